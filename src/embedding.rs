@@ -0,0 +1,224 @@
+//! The canonical embedding of Z[x]/(x^N + 1) into C^N, for noise analysis. For N a power
+//! of two, the roots of x^N + 1 are the odd powers zeta^(2k + 1) of zeta = e^(i pi / N),
+//! and slot k of the embedding evaluates a polynomial at zeta^(2k + 1). Elements of
+//! (Z/QZ)[x]/(x^N + 1) are embedded through their centered coefficients. The embedding
+//! is a ring homomorphism (products become slotwise products) as long as nothing wraps
+//! around mod Q.
+
+use crate::matrices::Matrix;
+use crate::poly::Poly;
+use crate::rings::modular::Modular;
+use std::array;
+use std::f64::consts::PI;
+use std::ops::{Add, Mul, Sub};
+
+/// A complex number, with just the arithmetic the embedding needs.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Complex { re, im }
+    }
+
+    /// The magnitude sqrt(re^2 + im^2).
+    pub fn abs(&self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// e^(i pi m / n), reducing m mod 2n first so large exponents keep their accuracy.
+    fn root_of_unity(m: i64, n: usize) -> Self {
+        let angle = PI * m.rem_euclid(2 * n as i64) as f64 / n as f64;
+        Complex::new(angle.cos(), angle.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, other: Self) -> Self::Output {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, other: Self) -> Self::Output {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, other: Self) -> Self::Output {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl<const Q: u32, const N: usize> Poly<Modular<Q>, N> {
+    /// The N slots sum_j a_j zeta^((2k + 1) j) over the centered coefficients a_j, by
+    /// direct evaluation in O(N^2).
+    pub fn canonical_embedding(&self) -> [Complex; N] {
+        let coeffs = self.centered_coeffs();
+        array::from_fn(|k| {
+            coeffs
+                .iter()
+                .enumerate()
+                .fold(Complex::default(), |acc, (j, &a)| {
+                    let root = Complex::root_of_unity(((2 * k + 1) * j) as i64, N);
+                    acc + root * Complex::new(a as f64, 0.0)
+                })
+        })
+    }
+
+    /// Inverse of canonical_embedding, up to floating point error: a_j is the real part of
+    /// (1/N) sum_k slot_k zeta^(-(2k + 1) j), rounded to the nearest integer and reduced
+    /// mod Q. Slots that are not the embedding of an integer polynomial (e.g. not closed
+    /// under conjugation) are rounded to a nearby one.
+    pub fn from_canonical_embedding(slots: &[Complex; N]) -> Self {
+        Poly(array::from_fn(|j| {
+            let sum = slots
+                .iter()
+                .enumerate()
+                .fold(Complex::default(), |acc, (k, &slot)| {
+                    acc + slot * Complex::root_of_unity(-(((2 * k + 1) * j) as i64), N)
+                });
+            Modular::from((sum.re / N as f64).round() as i64)
+        }))
+    }
+
+    /// The largest slot magnitude of the canonical embedding, max_k |a(zeta^(2k + 1))|.
+    /// This is the norm products behave well under, as ||a b|| <= ||a|| ||b|| slotwise.
+    /// In power-of-two cyclotomics it bounds the coefficient infinity norm from above,
+    /// since each a_j is an average of N slots times roots of unity, and is itself at most
+    /// the coefficient l1 norm sum_j |a_j|.
+    pub fn canonical_infinity_norm(&self) -> f64 {
+        self.canonical_embedding()
+            .iter()
+            .map(Complex::abs)
+            .fold(0.0, f64::max)
+    }
+}
+
+/// A matrix of complex numbers, e.g. one slot of the canonical embedding of a matrix over
+/// a polynomial ring.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ComplexMatrix<const ROWS: usize, const COLS: usize>(pub [[Complex; COLS]; ROWS]);
+
+impl<const ROWS: usize, const COLS: usize> ComplexMatrix<ROWS, COLS> {
+    /// Whether every entry is within tolerance of other's, in complex magnitude. The
+    /// embedding accumulates floating point error, so exact equality is rarely useful.
+    pub fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.0
+            .iter()
+            .flatten()
+            .zip(other.0.iter().flatten())
+            .all(|(a, b)| (*a - *b).abs() <= tolerance)
+    }
+}
+
+impl<const ROWS: usize, const COLS: usize, const K: usize> Mul<ComplexMatrix<COLS, K>>
+    for ComplexMatrix<ROWS, COLS>
+{
+    type Output = ComplexMatrix<ROWS, K>;
+    fn mul(self, other: ComplexMatrix<COLS, K>) -> Self::Output {
+        ComplexMatrix(array::from_fn(|i| {
+            array::from_fn(|j| {
+                (0..COLS).fold(Complex::default(), |acc, k| {
+                    acc + self.0[i][k] * other.0[k][j]
+                })
+            })
+        }))
+    }
+}
+
+impl<const Q: u32, const N: usize, const ROWS: usize, const COLS: usize>
+    Matrix<Poly<Modular<Q>, N>, ROWS, COLS>
+{
+    /// The canonical embedding entrywise, as N complex matrices: matrix k holds slot k of
+    /// every entry. Matrix products then become the N slotwise matrix products.
+    pub fn canonical_embedding(&self) -> [ComplexMatrix<ROWS, COLS>; N] {
+        let entries = self.0.map(|row| row.map(|p| p.canonical_embedding()));
+        array::from_fn(|k| ComplexMatrix(entries.map(|row| row.map(|slots| slots[k]))))
+    }
+
+    /// Inverse of canonical_embedding, rounding as Poly::from_canonical_embedding does.
+    pub fn from_canonical_embedding(slots: &[ComplexMatrix<ROWS, COLS>; N]) -> Self {
+        Matrix(array::from_fn(|i| {
+            array::from_fn(|j| {
+                Poly::from_canonical_embedding(&array::from_fn(|k| slots[k].0[i][j]))
+            })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::identities::One;
+    const Q: u32 = 12289;
+    type P = Poly<Modular<Q>, 8>;
+    #[test]
+    fn test_poly_embedding() {
+        let p = P::from([3, 1, 4, 1, 5, Q - 9, 2, Q - 6]);
+        let slots = p.canonical_embedding();
+        assert_eq!(P::from_canonical_embedding(&slots), p);
+        // x lands on zeta^(2k + 1), so every slot of x has magnitude 1
+        let x = P::from([0, 1, 0, 0, 0, 0, 0, 0]);
+        for (k, slot) in x.canonical_embedding().iter().enumerate() {
+            let angle = PI * (2 * k + 1) as f64 / 8.0;
+            assert!((*slot - Complex::new(angle.cos(), angle.sin())).abs() < 1e-12);
+        }
+        // Products of small polynomials are slotwise products
+        let q = P::from([1, Q - 2, 0, 0, 7, 0, 0, 1]);
+        let product = (p * q).canonical_embedding();
+        for ((a, b), c) in slots.iter().zip(q.canonical_embedding()).zip(product) {
+            assert!((*a * b - c).abs() < 1e-9);
+        }
+    }
+    #[test]
+    fn test_canonical_infinity_norm() {
+        // A constant c is c in every slot
+        assert!(
+            (P::from([Q - 7, 0, 0, 0, 0, 0, 0, 0]).canonical_infinity_norm() - 7.0).abs() < 1e-12
+        );
+        assert!((P::one().canonical_infinity_norm() - 1.0).abs() < 1e-12);
+        assert_eq!(
+            Poly::<Modular<Q>, 8>::from([0; 8]).canonical_infinity_norm(),
+            0.0
+        );
+        // Between the coefficient infinity and l1 norms
+        let p = P::from([3, 1, 4, 1, 5, Q - 9, 2, Q - 6]);
+        let norm = p.canonical_infinity_norm();
+        assert!(norm >= p.norm_inf() as f64);
+        assert!(norm <= 31.0 + 1e-9);
+    }
+    #[test]
+    fn test_matrix_embedding() {
+        let m = Matrix([
+            [P::from([1, 2, 0, 0, 0, 0, 0, Q - 1]), P::one()],
+            [
+                P::from([0, 0, 5, 0, 0, 0, 0, 0]),
+                P::from([Q - 3, 0, 0, 2, 0, 0, 0, 0]),
+            ],
+        ]);
+        let slots = m.canonical_embedding();
+        assert_eq!(Matrix::from_canonical_embedding(&slots), m);
+        // The embedding of m^2 is the square of each slot, up to rounding error
+        let squared = (m * m).canonical_embedding();
+        let zero = ComplexMatrix([[Complex::default(); 2]; 2]);
+        for (slot, expected) in slots.iter().zip(&squared) {
+            assert!((*slot * *slot).approx_eq(expected, 1e-9));
+            assert!(!(*slot * *slot).approx_eq(&zero, 1e-9));
+        }
+        let mut nudged = slots[0];
+        nudged.0[1][0].im += 1e-6;
+        assert!(nudged.approx_eq(&slots[0], 1e-5));
+        assert!(!nudged.approx_eq(&slots[0], 1e-7));
+    }
+}
@@ -1,6 +1,16 @@
-#![feature(min_const_generics)]
-mod matrices;
-mod rings;
+pub mod embedding;
+pub mod matrices;
+#[cfg(feature = "oracle")]
+pub mod oracle;
+pub mod poly;
+pub mod primes;
+pub mod rings;
+pub mod rlwe;
+pub mod rns;
+pub mod sampling;
+#[cfg(feature = "serde")]
+mod serialization;
+pub mod testing;
 
 #[macro_use]
 extern crate alga_derive;
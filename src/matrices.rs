@@ -1,119 +1,988 @@
 //! Matrix-Matrix and Matrix-Vector products, where matrices are defined over finite-rank
 //! commutative rings
 
-use crate::rings::FinRankCRing;
+use crate::poly::Poly;
+use crate::rings::modular::Modular;
+use crate::rings::{FinRankCRing, RingElement};
 use alga::general::*;
 use num_traits::identities::{One, Zero};
-use std::convert::From;
-use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use rand::distributions::{Distribution, Standard};
+use rand::Rng;
+use std::array;
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
 
-#[derive(Clone, PartialEq, Debug, Alga)]
-#[alga_traits(GroupAbelian(Additive))]
-struct Vector<R: Sized, const DIM: usize>(
-    [R; DIM],
-);
+#[derive(Clone, Copy, PartialEq, Debug, Alga)]
+#[alga_traits(GroupAbelian(Additive), Where = "R: RingCommutative + Copy")]
+pub struct Vector<R, const DIM: usize>(pub [R; DIM]);
 
-impl<R : FinRankCRing<N>, const N : u32, const DIM : usize> Add<Vector<R, DIM>> for Vector<R, DIM> {
+impl<R, const DIM: usize> AsRef<[R]> for Vector<R, DIM> {
+    fn as_ref(&self) -> &[R] {
+        &self.0
+    }
+}
+
+impl<R, const DIM: usize> AsMut<[R]> for Vector<R, DIM> {
+    fn as_mut(&mut self) -> &mut [R] {
+        &mut self.0
+    }
+}
+
+impl<R: RingCommutative + Copy, const DIM: usize> Add<Vector<R, DIM>> for Vector<R, DIM> {
     type Output = Vector<R, DIM>;
-    fn add(self, other : Self) -> Self::Output {
-        self.0.iter().zip(other.0.iter()).map(|(a, b)| a+b).collect()
+    fn add(self, other: Self) -> Self::Output {
+        Vector(array::from_fn(|i| self.0[i] + other.0[i]))
     }
 }
 
+impl<R: RingCommutative + Copy, const DIM: usize> Neg for Vector<R, DIM> {
+    type Output = Vector<R, DIM>;
+    fn neg(self) -> Self::Output {
+        Vector(self.0.map(|x| -x))
+    }
+}
 
+impl<R: RingCommutative + Copy, const DIM: usize> Sub<Vector<R, DIM>> for Vector<R, DIM> {
+    type Output = Vector<R, DIM>;
+    fn sub(self, other: Self) -> Self::Output {
+        Vector(array::from_fn(|i| self.0[i] - other.0[i]))
+    }
+}
 
+/// Coordinatewise constant-time equality, without short-circuiting on the first mismatch.
+#[cfg(feature = "subtle")]
+impl<R: subtle::ConstantTimeEq, const DIM: usize> subtle::ConstantTimeEq for Vector<R, DIM> {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        self.0[..].ct_eq(&other.0[..])
+    }
+}
 
-macro_rules! checked_opp {
-    ($func:ident, $bound:ident, $checked_func:ident) => {
-        impl<const Q: u32> $bound<Modular<Q>> for Modular<Q> {
-            type Output = Modular<Q>;
-            fn $func(self, other: Self) -> Self::Output {
-                if let None = u32::$checked_func(Q, Q) {
-                    // Less efficient case if func can overflow
-                    // As Q is const this is compiled away if not needed
-                    let x: u64 = self.0.into();
-                    let y: u64 = other.0.into();
-                    let modulus: u64 = Q.into();
-                    Modular::from([(u64::$func(x, y) % modulus) as u32])
-                } else {
-                    Modular::from([u32::$func(self.0, other.0)])
-                }
+#[cfg(feature = "subtle")]
+impl<R: subtle::ConditionallySelectable, const DIM: usize> subtle::ConditionallySelectable
+    for Vector<R, DIM>
+{
+    fn conditional_select(a: &Self, b: &Self, choice: subtle::Choice) -> Self {
+        Vector(array::from_fn(|i| {
+            R::conditional_select(&a.0[i], &b.0[i], choice)
+        }))
+    }
+}
+
+/// Scalar multiplication, scaling every coordinate by the same ring element.
+impl<R: RingCommutative + Copy, const DIM: usize> Mul<R> for Vector<R, DIM> {
+    type Output = Vector<R, DIM>;
+    fn mul(self, c: R) -> Self::Output {
+        Vector(self.0.map(|x| x * c))
+    }
+}
+
+impl<R: RingCommutative + Copy, const DIM: usize> AddAssign<Vector<R, DIM>> for Vector<R, DIM> {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other
+    }
+}
+
+impl<R: RingCommutative + Copy, const DIM: usize> SubAssign<Vector<R, DIM>> for Vector<R, DIM> {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other
+    }
+}
+
+/// Vectors with independent coordinates, each drawn from R's Standard distribution
+/// (uniform for Modular).
+impl<R, const DIM: usize> Distribution<Vector<R, DIM>> for Standard
+where
+    Standard: Distribution<R>,
+{
+    fn sample<G: Rng + ?Sized>(&self, rng: &mut G) -> Vector<R, DIM> {
+        Vector(array::from_fn(|_| rng.gen()))
+    }
+}
+
+impl<R, const DIM: usize> Vector<R, DIM>
+where
+    Standard: Distribution<R>,
+{
+    /// A random vector, see the Distribution impl.
+    pub fn random<G: Rng + ?Sized>(rng: &mut G) -> Self {
+        <Standard as Distribution<Self>>::sample(&Standard, rng)
+    }
+}
+
+impl<R: RingCommutative + Copy, const DIM: usize> Zero for Vector<R, DIM> {
+    fn zero() -> Self {
+        Vector([R::zero(); DIM])
+    }
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(|x| x.is_zero())
+    }
+}
+
+impl<R: Zero + Copy, const DIM: usize> Default for Vector<R, DIM> {
+    fn default() -> Self {
+        Vector([R::zero(); DIM])
+    }
+}
+
+impl<R: RingCommutative + Copy, const DIM: usize> Identity<Additive> for Vector<R, DIM> {
+    fn identity() -> Self {
+        Self::zero()
+    }
+}
+
+impl<R: RingCommutative + Copy, const DIM: usize> AbstractMagma<Additive> for Vector<R, DIM> {
+    fn operate(&self, other: &Self) -> Self {
+        *self + *other
+    }
+}
+
+impl<R: RingCommutative + Copy, const DIM: usize> TwoSidedInverse<Additive> for Vector<R, DIM> {
+    fn two_sided_inverse(&self) -> Self {
+        -*self
+    }
+}
+
+impl<R: RingCommutative + Copy, const DIM: usize> Vector<R, DIM> {
+    /// c * self, as a new vector
+    pub fn scale(&self, c: R) -> Self {
+        *self * c
+    }
+
+    /// Multiplies every coordinate by c, without building a new vector
+    pub fn scale_in_place(&mut self, c: R) {
+        for x in self.0.iter_mut() {
+            *x *= c;
+        }
+    }
+
+    /// The inner product sum_i self_i * other_i (0 when DIM = 0).
+    pub fn dot(&self, other: &Vector<R, DIM>) -> R {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .fold(R::zero(), |acc, (a, b)| acc + *a * *b)
+    }
+}
+
+impl<R: Zero, const DIM: usize> Vector<R, DIM> {
+    /// Whether exactly `weight` coordinates are nonzero, e.g. to validate an imported
+    /// fixed-Hamming-weight ternary secret.
+    pub fn verify_fixed_weight(&self, weight: usize) -> bool {
+        self.0.iter().filter(|x| !x.is_zero()).count() == weight
+    }
+}
+
+impl<R: RingElement, const DIM: usize> Vector<R, DIM> {
+    /// Raises each coordinate to the power e independently (not a ring operation on
+    /// vectors), e.g. for power sums of slot values.
+    pub fn coeff_pow(&self, e: u64) -> Self {
+        Vector(self.0.map(|x| x.pow(e)))
+    }
+}
+
+impl<const Q: u32, const DIM: usize> Vector<Modular<Q>, DIM> {
+    /// The bit pattern of a vector whose coordinates are all 0 or 1, such as a binary
+    /// LWE secret. Panics if any coordinate is neither 0 nor 1.
+    pub fn to_bits(&self) -> Vec<bool> {
+        self.0
+            .iter()
+            .map(|x| {
+                assert!(x.is_zero() || x.is_one(), "to_bits on a non-binary vector");
+                x.is_one()
+            })
+            .collect()
+    }
+
+    /// max_i |x_i| over the centered representatives in (-Q/2, Q/2].
+    pub fn norm_inf(&self) -> u64 {
+        self.0
+            .iter()
+            .map(|x| x.centered().unsigned_abs())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// sum_i x_i^2 over the centered representatives. This overflows (panicking in debug
+    /// builds) only for DIM * (Q/2)^2 >= 2^64.
+    pub fn norm_l2_squared(&self) -> u64 {
+        self.0
+            .iter()
+            .map(|x| x.centered().unsigned_abs().pow(2))
+            .sum()
+    }
+
+    /// Inverse of to_bits, or None if bits does not have exactly DIM entries.
+    pub fn from_bits(bits: &[bool]) -> Option<Self> {
+        if bits.len() != DIM {
+            return None;
+        }
+        let mut v = Self::zero();
+        for (x, b) in v.0.iter_mut().zip(bits) {
+            if *b {
+                *x = Modular::one();
             }
         }
-    };
+        Some(v)
+    }
+}
+
+/// A flat buffer whose length does not match the ROWS * COLS entries of a matrix.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LengthMismatch {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl fmt::Display for LengthMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "expected {} entries in row-major order, got {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for LengthMismatch {}
+
+/// A ROWS x COLS matrix, stored as an array of rows.
+#[derive(Clone, Copy, PartialEq, Debug, Alga)]
+#[alga_traits(GroupAbelian(Additive), Where = "R: RingCommutative + Copy")]
+pub struct Matrix<R, const ROWS: usize, const COLS: usize>(pub [[R; COLS]; ROWS]);
+
+/// The entries in row-major order, so entry (i, j) is at index i * COLS + j.
+impl<R, const ROWS: usize, const COLS: usize> AsRef<[R]> for Matrix<R, ROWS, COLS> {
+    fn as_ref(&self) -> &[R] {
+        self.0.as_flattened()
+    }
 }
-checked_opp!(add, Add, checked_add);
-checked_opp!(mul, Mul, checked_mul);
 
-impl<const Q: u32> Neg for Modular<Q> {
-    type Output = Modular<Q>;
+/// The entries in row-major order, as for AsRef.
+impl<R, const ROWS: usize, const COLS: usize> AsMut<[R]> for Matrix<R, ROWS, COLS> {
+    fn as_mut(&mut self) -> &mut [R] {
+        self.0.as_flattened_mut()
+    }
+}
+
+impl<R: Copy, const ROWS: usize, const COLS: usize> Matrix<R, ROWS, COLS> {
+    /// The matrix with the given entries in row-major order (the inverse of AsRef), or
+    /// an error unless data has exactly ROWS * COLS entries.
+    pub fn from_row_major(data: &[R]) -> Result<Self, LengthMismatch> {
+        if data.len() != ROWS * COLS {
+            return Err(LengthMismatch {
+                expected: ROWS * COLS,
+                actual: data.len(),
+            });
+        }
+        Ok(Matrix(array::from_fn(|i| {
+            array::from_fn(|j| data[i * COLS + j])
+        })))
+    }
+
+    /// The COLS x ROWS transpose
+    pub fn transpose(&self) -> Matrix<R, COLS, ROWS> {
+        Matrix(array::from_fn(|j| array::from_fn(|i| self.0[i][j])))
+    }
+
+    /// Row i, panicking if i >= ROWS
+    pub fn row(&self, i: usize) -> Vector<R, COLS> {
+        Vector(self.0[i])
+    }
+
+    /// Column j, panicking if j >= COLS
+    pub fn col(&self, j: usize) -> Vector<R, ROWS> {
+        Vector(self.0.map(|row| row[j]))
+    }
+}
+
+impl<R: RingCommutative + Copy, const ROWS: usize, const COLS: usize> Add<Matrix<R, ROWS, COLS>>
+    for Matrix<R, ROWS, COLS>
+{
+    type Output = Matrix<R, ROWS, COLS>;
+    fn add(self, other: Self) -> Self::Output {
+        Matrix(array::from_fn(|i| {
+            array::from_fn(|j| self.0[i][j] + other.0[i][j])
+        }))
+    }
+}
+
+impl<R: RingCommutative + Copy, const ROWS: usize, const COLS: usize> Neg
+    for Matrix<R, ROWS, COLS>
+{
+    type Output = Matrix<R, ROWS, COLS>;
     fn neg(self) -> Self::Output {
-        Modular::from([Q - self.0])
+        Matrix(self.0.map(|row| row.map(|x| -x)))
     }
 }
 
-impl<const Q: u32> Sub<Modular<Q>> for Modular<Q> {
-    type Output = Modular<Q>;
+impl<R: RingCommutative + Copy, const ROWS: usize, const COLS: usize> Sub<Matrix<R, ROWS, COLS>>
+    for Matrix<R, ROWS, COLS>
+{
+    type Output = Matrix<R, ROWS, COLS>;
     fn sub(self, other: Self) -> Self::Output {
-        self + other.neg()
+        Matrix(array::from_fn(|i| {
+            array::from_fn(|j| self.0[i][j] - other.0[i][j])
+        }))
+    }
+}
+
+impl<R: RingCommutative + Copy, const ROWS: usize, const COLS: usize>
+    AddAssign<Matrix<R, ROWS, COLS>> for Matrix<R, ROWS, COLS>
+{
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other
     }
 }
 
-macro_rules! op_assign {
-    ($func:ident, $bound:ident, $method:ident) => {
-        impl<const Q: u32> $bound<Modular<Q>> for Modular<Q> {
-            fn $func(&mut self, other: Self) {
-                *self = self.$method(other)
+impl<R: RingCommutative + Copy, const ROWS: usize, const COLS: usize>
+    SubAssign<Matrix<R, ROWS, COLS>> for Matrix<R, ROWS, COLS>
+{
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other
+    }
+}
+
+impl<R: RingCommutative + Copy, const ROWS: usize, const COLS: usize, const K: usize>
+    Mul<Matrix<R, COLS, K>> for Matrix<R, ROWS, COLS>
+{
+    type Output = Matrix<R, ROWS, K>;
+    fn mul(self, other: Matrix<R, COLS, K>) -> Self::Output {
+        let mut out = Matrix::zero();
+        for i in 0..ROWS {
+            for j in 0..K {
+                for k in 0..COLS {
+                    out.0[i][j] += self.0[i][k] * other.0[k][j];
+                }
             }
         }
-    };
+        out
+    }
+}
+
+impl<R: RingCommutative + Copy, const ROWS: usize, const COLS: usize> Mul<Vector<R, COLS>>
+    for Matrix<R, ROWS, COLS>
+{
+    type Output = Vector<R, ROWS>;
+    fn mul(self, v: Vector<R, COLS>) -> Self::Output {
+        Vector(self.0.map(|row| {
+            row.iter()
+                .zip(v.0.iter())
+                .fold(R::zero(), |acc, (a, b)| acc + *a * *b)
+        }))
+    }
 }
-op_assign!(add_assign, AddAssign, add);
-op_assign!(mul_assign, MulAssign, mul);
-op_assign!(sub_assign, SubAssign, sub);
 
-impl<const Q: u32> Zero for Modular<Q> {
+impl<R: RingCommutative + Copy, const ROWS: usize, const COLS: usize> Zero
+    for Matrix<R, ROWS, COLS>
+{
     fn zero() -> Self {
-        Modular(0)
+        Matrix([[R::zero(); COLS]; ROWS])
     }
     fn is_zero(&self) -> bool {
-        self.0 == 0
+        self.0.iter().flatten().all(|x| x.is_zero())
     }
 }
 
-impl<const Q: u32> One for Modular<Q> {
-    fn one() -> Self {
-        Modular(1)
-    }
-}
-// TODO: Write macros to generate the below
-// from Add, AddAssign, Mul, MulAssign, Sub, One, and Zero
-impl<const Q: u32> Identity<Additive> for Modular<Q> {
-    fn identity() -> Self {
-        Self::zero()
+impl<R: Zero + Copy, const ROWS: usize, const COLS: usize> Default for Matrix<R, ROWS, COLS> {
+    fn default() -> Self {
+        Matrix([[R::zero(); COLS]; ROWS])
     }
 }
 
-impl<const Q: u32> Identity<Multiplicative> for Modular<Q> {
+impl<R: RingCommutative + Copy, const ROWS: usize, const COLS: usize> Identity<Additive>
+    for Matrix<R, ROWS, COLS>
+{
     fn identity() -> Self {
-        Self::one()
+        Self::zero()
     }
 }
 
-impl<const Q: u32> AbstractMagma<Additive> for Modular<Q> {
+impl<R: RingCommutative + Copy, const ROWS: usize, const COLS: usize> AbstractMagma<Additive>
+    for Matrix<R, ROWS, COLS>
+{
     fn operate(&self, other: &Self) -> Self {
         *self + *other
     }
 }
 
-impl<const Q: u32> TwoSidedInverse<Additive> for Modular<Q> {
+impl<R: RingCommutative + Copy, const ROWS: usize, const COLS: usize> TwoSidedInverse<Additive>
+    for Matrix<R, ROWS, COLS>
+{
     fn two_sided_inverse(&self) -> Self {
-        Self::zero() - *self
+        -*self
     }
 }
 
-impl<const Q: u32> AbstractMagma<Multiplicative> for Modular<Q> {
-    fn operate(&self, other: &Self) -> Self {
-        *self * *other
+/// The N x N identity matrix. It is One rather than an inherent identity(), which would
+/// shadow (and disagree with) the additive Identity::identity() = 0.
+impl<R: RingCommutative + Copy, const N: usize> One for Matrix<R, N, N> {
+    fn one() -> Self {
+        Self::diagonal([R::one(); N])
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> Matrix<R, N, N> {
+    /// The determinant by the Leibniz expansion sum_sigma sign(sigma) prod_i
+    /// a_(i, sigma(i)), which needs only ring addition, multiplication and negation, so
+    /// works where division (and hence elimination) is unavailable, e.g. for polynomial
+    /// entries. It runs over all N! permutations (by Heap's algorithm, one transposition
+    /// each), so costs N! * N multiplications: panics for N > 8 rather than run for ever.
+    pub fn determinant_expansion(&self) -> R {
+        assert!(
+            N <= 8,
+            "determinant_expansion of a {0}x{0} matrix costs {0}!",
+            N
+        );
+        let term = |perm: &[usize; N]| {
+            perm.iter()
+                .enumerate()
+                .fold(R::one(), |acc, (i, &j)| acc * self.0[i][j])
+        };
+        let mut perm: [usize; N] = array::from_fn(|i| i);
+        let mut counters = [0; N];
+        let mut positive = true;
+        let mut det = term(&perm);
+        let mut i = 1;
+        while i < N {
+            if counters[i] < i {
+                let j = if i % 2 == 0 { 0 } else { counters[i] };
+                perm.swap(j, i);
+                positive = !positive;
+                let t = term(&perm);
+                det = if positive { det + t } else { det - t };
+                counters[i] += 1;
+                i = 1;
+            } else {
+                counters[i] = 0;
+                i += 1;
+            }
+        }
+        det
+    }
+
+    /// The diagonal matrix with the given diagonal entries, e.g. polynomials on the
+    /// diagonal of a structured module key. Multiplying a vector by it scales each
+    /// coordinate by the corresponding entry.
+    pub fn diagonal(entries: [R; N]) -> Self {
+        let mut out = Self::zero();
+        for (i, entry) in entries.iter().enumerate() {
+            out.0[i][i] = *entry;
+        }
+        out
+    }
+}
+
+/// The scalar matrix c * 1, for square matrices only.
+impl<R: RingCommutative + Copy, const N: usize> From<R> for Matrix<R, N, N> {
+    fn from(c: R) -> Self {
+        Self::diagonal([c; N])
+    }
+}
+
+/// The K x K matrix with the polynomials polys on its diagonal, as used for structured
+/// module keys and module gadgets. Works for any polynomial ring element type P (any
+/// commutative ring, in fact), and is Matrix::diagonal under the name module code uses.
+pub fn diagonal_module_matrix<P: RingCommutative + Copy, const K: usize>(
+    polys: [P; K],
+) -> Matrix<P, K, K> {
+    Matrix::diagonal(polys)
+}
+
+impl<const Q: u32, const N: usize, const ROWS: usize, const COLS: usize>
+    Matrix<Poly<Modular<Q>, N>, ROWS, COLS>
+{
+    /// Every coefficient of every entry as one contiguous buffer, e.g. for FFI: entries
+    /// in row-major order, each contributing its N coefficients constant term first.
+    pub fn flatten_coeffs(&self) -> Vec<u32> {
+        self.0
+            .iter()
+            .flatten()
+            .flat_map(|p| p.0.map(|c| c.to_coeffs()[0]))
+            .collect()
+    }
+
+    /// Inverse of flatten_coeffs, or None if coeffs does not have exactly
+    /// ROWS * COLS * N entries or any is outside [0, Q).
+    pub fn from_flat(coeffs: &[u32]) -> Option<Self> {
+        if coeffs.len() != ROWS * COLS * N {
+            return None;
+        }
+        let mut out = Self::zero();
+        let entries = out.0.iter_mut().flatten();
+        for (p, chunk) in entries.zip(coeffs.chunks_exact(N.max(1))) {
+            for (c, x) in p.0.iter_mut().zip(chunk) {
+                *c = Modular::deserialize_strict(*x).ok()?;
+            }
+        }
+        Some(out)
+    }
+}
+
+impl<const Q: u32, const ROWS: usize, const COLS: usize> Matrix<Modular<Q>, ROWS, COLS> {
+    /// The gadget matrix G = I_ROWS (x) g of Micciancio-Peikert, for the powers of two
+    /// g = (1, 2, 4, ..., 2^(k - 1)) with k = ceil(log2 Q): row i holds g in columns
+    /// i k through i k + k - 1, so G times the bit decompositions of an ROWS-vector's
+    /// entries is the vector itself. Panics unless COLS = ROWS * k.
+    pub fn gadget() -> Self {
+        let k = (32 - (Q - 1).leading_zeros()) as usize;
+        assert_eq!(
+            COLS,
+            ROWS * k,
+            "a gadget matrix for Q = {} has {} columns per row",
+            Q,
+            k
+        );
+        Matrix(array::from_fn(|i| {
+            array::from_fn(|j| {
+                if j / k == i {
+                    Modular::from([1 << (j % k)])
+                } else {
+                    Modular::zero()
+                }
+            })
+        }))
+    }
+
+    /// Overwrites every entry with a uniformly random element, as the Distribution impl of
+    /// Modular does, but drawing the randomness in bulk: one buffer of 4-byte words for
+    /// all entries, refilled (for just the entries still missing) when rejections use it
+    /// up. This amortizes the RNG calls over the whole matrix, e.g. for the public A of
+    /// module-LWE, and the output is a deterministic function of the RNG's stream.
+    pub fn fill_uniform<G: Rng + ?Sized>(&mut self, rng: &mut G) {
+        let modulus: u64 = Q.into();
+        let limit = (1 << 32) / modulus * modulus;
+        let mut buf = vec![0u8; 4 * ROWS * COLS];
+        let mut pos = buf.len();
+        let mut remaining = ROWS * COLS;
+        for entry in self.0.iter_mut().flatten() {
+            loop {
+                if pos == buf.len() {
+                    buf.truncate(4 * remaining);
+                    rng.fill(&mut buf[..]);
+                    pos = 0;
+                }
+                let word = [buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]];
+                let x = u32::from_le_bytes(word);
+                pos += 4;
+                if u64::from(x) < limit {
+                    *entry = Modular::from([x]);
+                    break;
+                }
+            }
+            remaining -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_vector_add() {
+        const Q: u32 = 13;
+        let x = Vector::<Modular<Q>, 4>([1, 5, 9, 12].map(|x| Modular::from([x])));
+        let y = Vector::<Modular<Q>, 4>([2, 8, 4, 12].map(|x| Modular::from([x])));
+        let x_plus_y = Vector([3, 0, 0, 11].map(|x| Modular::from([x])));
+        assert_eq!(x + y, x_plus_y);
+        assert_eq!(x_plus_y - y, x);
+        assert_eq!(x + (-x), Vector::zero());
+        let mut z = x;
+        z += y;
+        assert_eq!(z, x_plus_y);
+    }
+    #[cfg(feature = "subtle")]
+    #[test]
+    fn test_vector_constant_time() {
+        use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+        let m = |x| Modular::<13>::from([x]);
+        let (x, y) = (Vector([1, 2, 3].map(m)), Vector([1, 2, 4].map(m)));
+        assert!(bool::from(x.ct_eq(&x)));
+        assert!(!bool::from(x.ct_eq(&y)));
+        assert_eq!(Vector::conditional_select(&x, &y, Choice::from(0)), x);
+        assert_eq!(Vector::conditional_select(&x, &y, Choice::from(1)), y);
+    }
+    #[test]
+    fn test_vector_additive_group() {
+        const Q: u32 = 13;
+        let x = Vector::<Modular<Q>, 3>([1, 5, 12].map(|x| Modular::from([x])));
+        let y = Vector::<Modular<Q>, 3>([2, 8, 4].map(|x| Modular::from([x])));
+        assert_eq!(
+            <Vector<Modular<Q>, 3> as Identity<Additive>>::identity(),
+            Vector::zero()
+        );
+        assert_eq!(AbstractMagma::<Additive>::operate(&x, &y), x + y);
+        assert_eq!(
+            x + TwoSidedInverse::<Additive>::two_sided_inverse(&x),
+            Vector::zero()
+        );
+    }
+    #[test]
+    fn test_vector_default() {
+        const Q: u32 = 13;
+        let v = Vector::<Modular<Q>, 4>::default();
+        assert_eq!(v, Vector([Modular::zero(); 4]));
+    }
+    #[test]
+    fn test_vector_scale() {
+        const Q: u32 = 11;
+        let m = |x| Modular::<Q>::from([x]);
+        let v = Vector([2, 3].map(m));
+        assert_eq!(v * m(4), Vector([8, 1].map(m)));
+        assert_eq!(v.scale(m(4)), v * m(4));
+        // Scaling a module element by a polynomial
+        type P = crate::poly::Poly<Modular<Q>, 4>;
+        let x = P::from([0, 1, 0, 0]);
+        let w = Vector([P::one(), P::from([0, 0, 0, 2])]);
+        assert_eq!(w * x, Vector([x, P::from([Q - 2, 0, 0, 0])]));
+    }
+    #[test]
+    fn test_vector_scale_in_place() {
+        const Q: u32 = 11;
+        let c = Modular::<Q>::from([4]);
+        let coords = [2, 3, 7, 10].map(|x| Modular::<Q>::from([x]));
+        let mut v = Vector(coords);
+        v.scale_in_place(c);
+        assert_eq!(v, Vector(coords.map(|x| x * c)));
+    }
+    #[test]
+    fn test_vector_dot() {
+        const Q: u32 = 97;
+        let m = |x| Modular::<Q>::from([x]);
+        let a = Vector([1, 2, 3].map(m));
+        let b = Vector([4, 5, 6].map(m));
+        assert_eq!(a.dot(&b), m(32));
+        assert_eq!(a.dot(&b), b.dot(&a));
+        assert_eq!(Vector::<Modular<Q>, 0>([]).dot(&Vector([])), m(0));
+        // Module-LWE style, over polynomial coordinates
+        type P = crate::poly::Poly<Modular<Q>, 4>;
+        let x = P::from([0, 1, 0, 0]);
+        let u = Vector([x, P::one()]);
+        let v = Vector([x * x * x, P::from([5, 0, 0, 0])]);
+        // x * x^3 + 5 = -1 + 5
+        assert_eq!(u.dot(&v), P::from([4, 0, 0, 0]));
+    }
+    #[test]
+    fn test_vector_is_zero() {
+        const Q: u32 = 13;
+        let mut v = Vector::<Modular<Q>, 4>::zero();
+        assert!(v.is_zero());
+        v.0[2] = Modular::from([1]);
+        assert!(!v.is_zero());
+    }
+    #[test]
+    fn test_vector_fixed_weight() {
+        const Q: u32 = 13;
+        // A ternary secret with three nonzero coordinates
+        let v = Vector::<Modular<Q>, 6>([0, 1, Q - 1, 0, 0, 1].map(|x| Modular::from([x])));
+        assert!(v.verify_fixed_weight(3));
+        assert!(!v.verify_fixed_weight(2));
+        assert!(Vector::<Modular<Q>, 6>::zero().verify_fixed_weight(0));
+    }
+    #[test]
+    fn test_vector_coeff_pow() {
+        const Q: u32 = 13;
+        let v = Vector::<Modular<Q>, 4>([0, 2, 5, 12].map(|x| Modular::from([x])));
+        let squares = v.coeff_pow(2);
+        for i in 0..4 {
+            assert_eq!(squares.0[i], v.0[i].pow(2));
+        }
+        assert_eq!(squares, Vector([0, 4, 12, 1].map(|x| Modular::from([x]))));
+        assert_eq!(v.coeff_pow(0), Vector([Modular::one(); 4]));
+    }
+    #[test]
+    fn test_vector_norms() {
+        const Q: u32 = 13;
+        // Centered: (-3, 6, 0, -6)
+        let v = Vector::<Modular<Q>, 4>([10, 6, 0, 7].map(|x| Modular::from([x])));
+        assert_eq!(v.norm_inf(), 6);
+        assert_eq!(v.norm_l2_squared(), 9 + 36 + 36);
+        assert_eq!(Vector::<Modular<Q>, 0>([]).norm_inf(), 0);
+    }
+    #[test]
+    fn test_vector_bits() {
+        const Q: u32 = 13;
+        let bits = vec![true, false, false, true, true];
+        let v = Vector::<Modular<Q>, 5>::from_bits(&bits).unwrap();
+        assert_eq!(v, Vector([1, 0, 0, 1, 1].map(|x| Modular::from([x]))));
+        assert_eq!(v.to_bits(), bits);
+        assert_eq!(Vector::<Modular<Q>, 4>::from_bits(&bits), None);
+    }
+    #[test]
+    #[should_panic]
+    fn test_vector_bits_non_binary() {
+        Vector::<Modular<13>, 2>([Modular::from([1]), Modular::from([2])]).to_bits();
+    }
+    #[test]
+    fn test_vector_random() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let v = Vector::<Modular<3329>, 64>::random(&mut rng);
+        assert_ne!(v, Vector::<Modular<3329>, 64>::random(&mut rng));
+    }
+    #[test]
+    fn test_as_slice() {
+        const Q: u32 = 13;
+        fn sum(xs: &[Modular<Q>]) -> Modular<Q> {
+            xs.iter().fold(Modular::zero(), |acc, x| acc + *x)
+        }
+        let m = |x| Modular::<Q>::from([x]);
+        let mut v = Vector([1, 2, 3, 4].map(m));
+        assert_eq!(sum(v.as_ref()), m(10));
+        v.as_mut()[0] = m(5);
+        assert_eq!(v, Vector([5, 2, 3, 4].map(m)));
+        let mut a = Matrix([[1, 2, 3], [4, 5, 6]].map(|row| row.map(m)));
+        assert_eq!(a.as_ref(), &[1, 2, 3, 4, 5, 6].map(m));
+        // Row-major: index 1 * 3 + 0 is entry (1, 0)
+        a.as_mut()[3] = m(0);
+        assert_eq!(a.0[1][0], m(0));
+    }
+    #[test]
+    fn test_matrix_from_row_major() {
+        let m = |x| Modular::<13>::from([x]);
+        let data = [1, 2, 3, 4, 5, 6].map(m);
+        let a = Matrix::<_, 2, 3>::from_row_major(&data).unwrap();
+        assert_eq!(a, Matrix([[1, 2, 3], [4, 5, 6]].map(|row| row.map(m))));
+        assert_eq!(a.as_ref(), &data);
+        assert_eq!(
+            Matrix::<Modular<13>, 2, 3>::from_row_major(&data[..5]),
+            Err(LengthMismatch {
+                expected: 6,
+                actual: 5
+            })
+        );
+        // Round trip through the entries of a matrix of polynomials
+        type P = Poly<Modular<97>, 2>;
+        let b = Matrix([[P::from([1, 2]), P::from([3, 4])], [P::one(), P::zero()]]);
+        let c = Matrix::<P, 2, 2>::from_row_major(b.as_ref()).unwrap();
+        assert_eq!(c, b);
+        assert_eq!(c.flatten_coeffs(), b.flatten_coeffs());
+    }
+    #[test]
+    fn test_matrix_mul() {
+        const Q: u32 = 97;
+        let m = |x| Modular::<Q>::from([x]);
+        let a = Matrix([[1, 2, 3], [4, 5, 6]].map(|row| row.map(m)));
+        let b = Matrix([[7, 8], [9, 10], [11, 12]].map(|row| row.map(m)));
+        // [[58, 64], [139, 154]] reduced mod 97
+        let ab = Matrix([[58, 64], [42, 57]].map(|row| row.map(m)));
+        assert_eq!(a * b, ab);
+        assert_eq!(Matrix::one() * a, a);
+        assert_eq!(a * Matrix::one(), a);
+        assert!(Matrix::<Modular<Q>, 3, 3>::one().is_one());
+        assert_ne!(
+            Matrix::<Modular<Q>, 3, 3>::one(),
+            <Matrix<Modular<Q>, 3, 3> as Identity<Additive>>::identity()
+        );
+        assert_eq!(a + Matrix::zero(), a);
+        assert!((a * Matrix::<Modular<Q>, 3, 5>::zero()).is_zero());
+    }
+    #[test]
+    fn test_matrix_additive_group() {
+        const Q: u32 = 13;
+        let m = |x| Modular::<Q>::from([x]);
+        let a = Matrix([[1, 5, 12], [0, 7, 3]].map(|row| row.map(m)));
+        let b = Matrix([[2, 8, 4], [12, 6, 10]].map(|row| row.map(m)));
+        let a_plus_b = Matrix([[3, 0, 3], [12, 0, 0]].map(|row| row.map(m)));
+        assert_eq!(a + b, a_plus_b);
+        assert_eq!(a_plus_b - b, a);
+        assert_eq!(-a, Matrix([[12, 8, 1], [0, 6, 10]].map(|row| row.map(m))));
+        let mut c = a;
+        c += b;
+        assert_eq!(c, a_plus_b);
+        c -= b;
+        assert_eq!(c, a);
+        assert_eq!(
+            <Matrix<Modular<Q>, 2, 3> as Identity<Additive>>::identity(),
+            Matrix::zero()
+        );
+        assert_eq!(AbstractMagma::<Additive>::operate(&a, &b), a + b);
+        assert_eq!(
+            a + TwoSidedInverse::<Additive>::two_sided_inverse(&a),
+            Matrix::zero()
+        );
+    }
+    #[test]
+    fn test_matrix_default() {
+        const Q: u32 = 13;
+        let a = Matrix::<Modular<Q>, 2, 3>::default();
+        assert_eq!(a, Matrix([[Modular::zero(); 3]; 2]));
+        assert_eq!(a, Matrix::zero());
+    }
+    #[test]
+    fn test_matrix_diagonal() {
+        const Q: u32 = 97;
+        type P = crate::poly::Poly<Modular<Q>, 4>;
+        let polys = [
+            P::from([1, 2, 0, 0]),
+            P::from([0, 0, 0, 1]),
+            P::from([5, 0, 0, 0]),
+        ];
+        let d = diagonal_module_matrix(polys);
+        assert_eq!(d.0[0][1], P::zero());
+        assert_eq!(d.0[2][2], polys[2]);
+        let v = Vector([P::from([3, 0, 0, 1]), P::from([0, 1, 0, 0]), P::one()]);
+        assert_eq!(d * v, Vector(array::from_fn(|i| polys[i] * v.0[i])));
+        assert_eq!(
+            Matrix::<Modular<Q>, 3, 3>::diagonal([Modular::one(); 3]),
+            Matrix::one()
+        );
+    }
+    #[test]
+    fn test_matrix_from_scalar() {
+        const Q: u32 = 97;
+        let c = Modular::<Q>::from([5]);
+        let v = Vector([1, 2, 96].map(|x| Modular::<Q>::from([x])));
+        assert_eq!(Matrix::from(c) * v, v.scale(c));
+        assert_eq!(Matrix::<_, 3, 3>::from(Modular::<Q>::one()), Matrix::one());
+        assert!(Matrix::<_, 2, 2>::from(Modular::<Q>::zero()).is_zero());
+    }
+    #[test]
+    fn test_determinant_expansion() {
+        const Q: u32 = 97;
+        let m = |x| Modular::<Q>::from([x]);
+        // det [[2, 0, 1], [1, 3, 2], [1, 1, 1]] = 2 (3 - 2) - 0 + 1 (1 - 3) = 0 ...
+        let a = Matrix([[2, 0, 1], [1, 3, 2], [1, 1, 1]].map(|row| row.map(m)));
+        assert!(a.determinant_expansion().is_zero());
+        // ... and det [[2, 5, 1], [1, 3, 2], [1, 1, 4]] = 2 * 10 - 5 * 2 + 1 * (-2) = 8
+        let b = Matrix([[2, 5, 1], [1, 3, 2], [1, 1, 4]].map(|row| row.map(m)));
+        assert_eq!(b.determinant_expansion(), m(8));
+        assert_eq!((a * b).determinant_expansion(), Modular::zero());
+        assert_eq!(
+            Matrix::<Modular<Q>, 5, 5>::one().determinant_expansion(),
+            m(1)
+        );
+        // A 4-cycle is an odd permutation, and its square even
+        let cycle = [[0, 1, 0, 0], [0, 0, 1, 0], [0, 0, 0, 1], [1, 0, 0, 0]];
+        let cycle = Matrix(cycle.map(|row| row.map(m)));
+        assert_eq!(cycle.determinant_expansion(), -m(1));
+        assert_eq!((cycle * cycle).determinant_expansion(), m(1));
+        assert_eq!(
+            Matrix::<Modular<Q>, 0, 0>::one().determinant_expansion(),
+            m(1)
+        );
+        // Polynomial entries, against the 3x3 cofactor (Sarrus) expansion by hand
+        type P = Poly<Modular<Q>, 4>;
+        let e = [
+            [
+                P::from([1, 2, 0, 0]),
+                P::from([0, 1, 0, 0]),
+                P::from([3, 0, 0, 1]),
+            ],
+            [
+                P::from([0, 0, 1, 0]),
+                P::from([5, 0, 0, 0]),
+                P::from([1, 1, 1, 1]),
+            ],
+            [
+                P::from([2, 0, 0, 0]),
+                P::from([0, 0, 0, 1]),
+                P::from([4, 0, 96, 0]),
+            ],
+        ];
+        let sarrus =
+            e[0][0] * e[1][1] * e[2][2] + e[0][1] * e[1][2] * e[2][0] + e[0][2] * e[1][0] * e[2][1]
+                - e[0][2] * e[1][1] * e[2][0]
+                - e[0][0] * e[1][2] * e[2][1]
+                - e[0][1] * e[1][0] * e[2][2];
+        assert_eq!(Matrix(e).determinant_expansion(), sarrus);
+    }
+    #[test]
+    fn test_gadget() {
+        // Q = 13 needs k = 4 bits, so g = (1, 2, 4, 8)
+        let g = Matrix::<Modular<13>, 2, 8>::gadget();
+        assert_eq!(
+            g.row(0),
+            Vector([1, 2, 4, 8, 0, 0, 0, 0].map(|x| Modular::from([x])))
+        );
+        assert_eq!(
+            g.row(1),
+            Vector([0, 0, 0, 0, 1, 2, 4, 8].map(|x| Modular::from([x])))
+        );
+        // G recomposes the bits of (11, 6)
+        let bits = Vector([1, 1, 0, 1, 0, 1, 1, 0].map(|x| Modular::from([x])));
+        assert_eq!(g * bits, Vector([11, 6].map(|x| Modular::from([x]))));
+        // A power of two needs no extra bit
+        assert_eq!(
+            Matrix::<Modular<16>, 1, 4>::gadget().row(0).0[3],
+            Modular::from([8])
+        );
+    }
+    #[test]
+    #[should_panic(expected = "has 4 columns per row")]
+    fn test_gadget_wrong_width() {
+        Matrix::<Modular<13>, 2, 7>::gadget();
+    }
+    #[test]
+    fn test_matrix_fill_uniform() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+        // About a quarter of the draws are rejected for this Q, so the buffer is refilled
+        const BIG: u32 = 3221225473;
+        let mut a = Matrix::<Modular<BIG>, 8, 8>::zero();
+        a.fill_uniform(&mut StdRng::seed_from_u64(7));
+        let mut b = Matrix::zero();
+        b.fill_uniform(&mut StdRng::seed_from_u64(7));
+        assert_eq!(a, b);
+        b.fill_uniform(&mut StdRng::seed_from_u64(8));
+        assert_ne!(a, b);
+        // Every residue of Z/7Z turns up about equally often
+        let mut rng = StdRng::seed_from_u64(9);
+        let mut counts = [0; 7];
+        for _ in 0..100 {
+            let mut m = Matrix::<Modular<7>, 10, 10>::zero();
+            m.fill_uniform(&mut rng);
+            for x in m.as_ref() {
+                counts[x.to_coeffs()[0] as usize] += 1;
+            }
+        }
+        // 10000 draws, so about 1429 each, with a standard deviation of about 35
+        assert!(
+            counts.iter().all(|&c| (1250..1600).contains(&c)),
+            "{:?}",
+            counts
+        );
+    }
+    #[test]
+    fn test_matrix_flatten_coeffs() {
+        const Q: u32 = 97;
+        type P = Poly<Modular<Q>, 2>;
+        let a = Matrix([
+            [P::from([1, 2]), P::from([3, 4]), P::from([5, 6])],
+            [P::from([7, 8]), P::from([9, 10]), P::from([11, 96])],
+        ]);
+        let flat = a.flatten_coeffs();
+        assert_eq!(flat, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 96]);
+        assert_eq!(Matrix::from_flat(&flat), Some(a));
+        assert_eq!(Matrix::<P, 2, 3>::from_flat(&flat[1..]), None);
+        let mut bad = flat;
+        bad[11] = Q;
+        assert_eq!(Matrix::<P, 2, 3>::from_flat(&bad), None);
+    }
+    #[test]
+    fn test_matrix_transpose() {
+        const Q: u32 = 97;
+        let m = |x| Modular::<Q>::from([x]);
+        let a = Matrix([[1, 2, 3], [4, 5, 6]].map(|row| row.map(m)));
+        let t = a.transpose();
+        for i in 0..2 {
+            for j in 0..3 {
+                assert_eq!(t.0[j][i], a.0[i][j]);
+            }
+        }
+        assert_eq!(t, Matrix([[1, 4], [2, 5], [3, 6]].map(|row| row.map(m))));
+        assert_eq!(t.transpose(), a);
+        assert_eq!(a.row(1), Vector([4, 5, 6].map(m)));
+        assert_eq!(a.col(2), Vector([3, 6].map(m)));
+        assert_eq!(t.row(2), a.col(2));
+    }
+    #[test]
+    fn test_matrix_vector_mul() {
+        const Q: u32 = 257;
+        let m = |x| Modular::<Q>::from([x]);
+        let a = Matrix([[1, 2], [30, 40], [200, 100]].map(|row| row.map(m)));
+        let s = Vector([5, 7].map(m));
+        // (19, 430, 1700) reduced mod 257
+        assert_eq!(a * s, Vector([19, 173, 158].map(m)));
+        assert_eq!(Matrix::one() * s, s);
     }
 }
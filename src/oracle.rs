@@ -0,0 +1,70 @@
+//! Reference implementations of the Modular arithmetic, for differential testing and
+//! fuzzing. These take the modulus at runtime and compute in 128-bit integers, where
+//! nothing can overflow, reducing with a plain `%` at the end. They are deliberately
+//! naive: their only job is to be obviously correct for every q, so that the fast paths
+//! (which pick different code depending on the size of Q) can be checked against them.
+
+/// (a + b) mod q
+pub fn add_ref(q: u32, a: u32, b: u32) -> u32 {
+    ((u128::from(a) + u128::from(b)) % u128::from(q)) as u32
+}
+
+/// (a - b) mod q, as a representative in [0, q)
+pub fn sub_ref(q: u32, a: u32, b: u32) -> u32 {
+    (i128::from(a) - i128::from(b)).rem_euclid(i128::from(q)) as u32
+}
+
+/// (a * b) mod q
+pub fn mul_ref(q: u32, a: u32, b: u32) -> u32 {
+    ((u128::from(a) * u128::from(b)) % u128::from(q)) as u32
+}
+
+/// -a mod q, as a representative in [0, q)
+pub fn neg_ref(q: u32, a: u32) -> u32 {
+    (-i128::from(a)).rem_euclid(i128::from(q)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rings::modular::Modular;
+    use crate::rings::FinRankCRing;
+    use proptest::prelude::*;
+
+    // Q is a const generic, so "random Q" is a spread of moduli on either side of the
+    // thresholds (2^16 for multiplication, 2^31 for addition) where Modular changes strategy.
+    macro_rules! oracle_proptests {
+        ($($name:ident: $q:expr,)*) => {
+            $(
+                mod $name {
+                    use super::*;
+                    const Q: u32 = $q;
+                    fn m(x: u32) -> Modular<Q> {
+                        Modular::from([x])
+                    }
+                    proptest! {
+                        #[test]
+                        fn agrees_with_oracle(a in any::<u32>(), b in any::<u32>()) {
+                            let (a_red, b_red) = (a % Q, b % Q);
+                            prop_assert_eq!((m(a) + m(b)).to_coeffs(), [add_ref(Q, a_red, b_red)]);
+                            prop_assert_eq!((m(a) - m(b)).to_coeffs(), [sub_ref(Q, a_red, b_red)]);
+                            prop_assert_eq!((m(a) * m(b)).to_coeffs(), [mul_ref(Q, a_red, b_red)]);
+                            prop_assert_eq!((-m(a)).to_coeffs(), [neg_ref(Q, a_red)]);
+                        }
+                    }
+                }
+            )*
+        };
+    }
+    oracle_proptests! {
+        q_2: 2,
+        q_13: 13,
+        q_3329: 3329,
+        q_65535: 65535,
+        q_65537: 65537,
+        q_8380417: 8380417,
+        q_2_31_minus_1: 2147483647,
+        q_2_31_plus_11: 2147483659,
+        q_u32_max: 4294967295,
+    }
+}
@@ -0,0 +1,1933 @@
+//! The negacyclic polynomial ring R[x]/(x^N + 1), the ring RLWE is defined over.
+//! When N is a power of two and R = Z/qZ, x^N + 1 is the 2N-th cyclotomic polynomial, and
+//! the ring is rank N over R (so it is a FinRankCRing<N> when R is Z/qZ).
+
+use crate::matrices::Matrix;
+use crate::rings::modular::Modular;
+use crate::rings::{FinRankCRing, FromBase, RingElement};
+use alga::general::*;
+use num_traits::identities::{One, Zero};
+use rand::distributions::{Distribution, Standard};
+use rand::Rng;
+use std::array;
+use std::convert::From;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// An element of R[x]/(x^N + 1), stored as its coefficients [a_0, ..., a_(N-1)] in the
+/// basis 1, x, ..., x^(N-1).
+#[derive(Clone, Copy, PartialEq, Debug, Alga)]
+#[alga_traits(
+    RingCommutative(Additive, Multiplicative),
+    Where = "R: RingCommutative + Copy"
+)]
+pub struct Poly<R, const N: usize>(pub [R; N]);
+
+/// The coefficients, constant term first.
+impl<R, const N: usize> AsRef<[R]> for Poly<R, N> {
+    fn as_ref(&self) -> &[R] {
+        &self.0
+    }
+}
+
+impl<R, const N: usize> AsMut<[R]> for Poly<R, N> {
+    fn as_mut(&mut self) -> &mut [R] {
+        &mut self.0
+    }
+}
+
+/// Coefficientwise constant-time equality, without short-circuiting on the first mismatch.
+#[cfg(feature = "subtle")]
+impl<R: subtle::ConstantTimeEq, const N: usize> subtle::ConstantTimeEq for Poly<R, N> {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        self.0[..].ct_eq(&other.0[..])
+    }
+}
+
+#[cfg(feature = "subtle")]
+impl<R: subtle::ConditionallySelectable, const N: usize> subtle::ConditionallySelectable
+    for Poly<R, N>
+{
+    fn conditional_select(a: &Self, b: &Self, choice: subtle::Choice) -> Self {
+        Poly(array::from_fn(|i| {
+            R::conditional_select(&a.0[i], &b.0[i], choice)
+        }))
+    }
+}
+
+impl<R: FinRankCRing<1>, const N: usize> From<[u32; N]> for Poly<R, N> {
+    fn from(x: [u32; N]) -> Self {
+        Poly(x.map(|c| R::from_coeffs([c])))
+    }
+}
+
+impl<R: FinRankCRing<1> + Copy, const N: usize> FinRankCRing<N> for Poly<R, N> {
+    fn to_coeffs(&self) -> [u32; N] {
+        self.0.map(|c| c.to_coeffs()[0])
+    }
+}
+
+/// The constant polynomial s.
+impl<R: RingCommutative + Copy, const N: usize> FromBase<R> for Poly<R, N> {
+    fn from_base(s: R) -> Self {
+        let mut out = Self::zero();
+        // For N = 0 this is the zero ring, where every s maps to 0
+        if N > 0 {
+            out.0[0] = s;
+        }
+        out
+    }
+}
+
+/// Polynomials with independent coefficients, each drawn from R's Standard distribution
+/// (uniform for Modular).
+impl<R, const N: usize> Distribution<Poly<R, N>> for Standard
+where
+    Standard: Distribution<R>,
+{
+    fn sample<G: Rng + ?Sized>(&self, rng: &mut G) -> Poly<R, N> {
+        Poly(array::from_fn(|_| rng.gen()))
+    }
+}
+
+impl<R, const N: usize> Poly<R, N>
+where
+    Standard: Distribution<R>,
+{
+    /// A random polynomial, see the Distribution impl.
+    pub fn random<G: Rng + ?Sized>(rng: &mut G) -> Self {
+        <Standard as Distribution<Self>>::sample(&Standard, rng)
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> Add<Poly<R, N>> for Poly<R, N> {
+    type Output = Poly<R, N>;
+    fn add(self, other: Self) -> Self::Output {
+        Poly(array::from_fn(|i| self.0[i] + other.0[i]))
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> Sub<Poly<R, N>> for Poly<R, N> {
+    type Output = Poly<R, N>;
+    fn sub(self, other: Self) -> Self::Output {
+        Poly(array::from_fn(|i| self.0[i] - other.0[i]))
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> Neg for Poly<R, N> {
+    type Output = Poly<R, N>;
+    fn neg(self) -> Self::Output {
+        Poly(self.0.map(|c| -c))
+    }
+}
+
+/// Schoolbook negacyclic convolution: x^i * x^j is x^(i + j) if i + j < N, and
+/// -x^(i + j - N) otherwise, as x^N = -1.
+impl<R: RingCommutative + Copy, const N: usize> Mul<Poly<R, N>> for Poly<R, N> {
+    type Output = Poly<R, N>;
+    fn mul(self, other: Self) -> Self::Output {
+        let mut out = [R::zero(); N];
+        for (i, a) in self.0.iter().enumerate() {
+            for (j, b) in other.0.iter().enumerate() {
+                if i + j < N {
+                    out[i + j] += *a * *b;
+                } else {
+                    out[i + j - N] -= *a * *b;
+                }
+            }
+        }
+        Poly(out)
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> Poly<R, N> {
+    /// a * self + b in one pass: the schoolbook product accumulates straight into b's
+    /// coefficients. For operands already in the NTT domain, see affine_ntt.
+    pub fn affine(&self, a: &Self, b: &Self) -> Self {
+        let mut out = b.0;
+        for (i, x) in a.0.iter().enumerate() {
+            for (j, y) in self.0.iter().enumerate() {
+                if i + j < N {
+                    out[i + j] += *x * *y;
+                } else {
+                    out[i + j - N] -= *x * *y;
+                }
+            }
+        }
+        Poly(out)
+    }
+}
+
+/// Below this many coefficients Karatsuba recursion falls back to the schoolbook product.
+const KARATSUBA_THRESHOLD: usize = 16;
+
+/// The full product of a and b in R[x] (length a.len() + b.len() - 1, or empty if either
+/// is), by Karatsuba's three half-size products. For equal lengths.
+fn karatsuba<R: RingCommutative + Copy>(a: &[R], b: &[R]) -> Vec<R> {
+    let n = a.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let mut out = vec![R::zero(); 2 * n - 1];
+    if n <= KARATSUBA_THRESHOLD {
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                out[i + j] += *x * *y;
+            }
+        }
+        return out;
+    }
+    let (z0, z1, z2, h) = karatsuba_parts(a, b);
+    for (i, c) in z0.iter().enumerate() {
+        out[i] += *c;
+    }
+    for (i, c) in z1.iter().enumerate() {
+        out[h + i] += *c;
+    }
+    for (i, c) in z2.iter().enumerate() {
+        out[2 * h + i] += *c;
+    }
+    out
+}
+
+/// The products z0 = a0 b0, z1 = (a0 + a1)(b0 + b1) - z0 - z2 and z2 = a1 b1 for the split
+/// a = a0 + a1 x^h at h = floor(n/2), so that a b = z0 + z1 x^h + z2 x^(2h). a1 and b1 are
+/// zero-padded to a1's length n - h when n is odd.
+fn karatsuba_parts<R: RingCommutative + Copy>(a: &[R], b: &[R]) -> (Vec<R>, Vec<R>, Vec<R>, usize) {
+    let h = a.len() / 2;
+    let (a0, a1) = a.split_at(h);
+    let (b0, b1) = b.split_at(h);
+    let pad = |x: &[R]| {
+        let mut x = x.to_vec();
+        x.resize(a.len() - h, R::zero());
+        x
+    };
+    let add = |x: &[R], y: &[R]| {
+        let (x, y) = (pad(x), pad(y));
+        x.iter().zip(&y).map(|(p, q)| *p + *q).collect::<Vec<_>>()
+    };
+    let z0 = karatsuba(a0, b0);
+    let z2 = karatsuba(a1, b1);
+    let mut z1 = karatsuba(&add(a0, a1), &add(b0, b1));
+    for (i, c) in z0.iter().enumerate() {
+        z1[i] -= *c;
+    }
+    for (i, c) in z2.iter().enumerate() {
+        z1[i] -= *c;
+    }
+    (z0, z1, z2, h)
+}
+
+/// Reduces a full product in R[x] (of length at most 2N - 1) mod x^N + 1: coefficient i
+/// gets c_i - c_(i + N). A separate pass over the product, which mul_karatsuba fuses into
+/// its final combine.
+pub fn negacyclic_reduce<R: RingCommutative + Copy, const N: usize>(full: &[R]) -> Poly<R, N> {
+    assert!(
+        full.len() < 2 * N.max(1),
+        "a product of length {} needs only 2N - 1 coefficients",
+        full.len()
+    );
+    let mut out = [R::zero(); N];
+    for (i, c) in full.iter().enumerate() {
+        if i < N {
+            out[i] += *c;
+        } else {
+            out[i - N] -= *c;
+        }
+    }
+    Poly(out)
+}
+
+impl<R: RingCommutative + Copy, const N: usize> Poly<R, N> {
+    /// Karatsuba multiplication, agreeing with the schoolbook Mul. The half-size products
+    /// recurse in R[x], but the top level reduces as it combines them: each of z0, z1 x^h
+    /// and z2 x^(2h) is added straight into the N output coefficients (subtracting where
+    /// it passes x^N = -1), rather than first assembling the 2N - 1 coefficient product.
+    pub fn mul_karatsuba(&self, other: &Self) -> Self {
+        if N <= KARATSUBA_THRESHOLD {
+            return *self * *other;
+        }
+        let (z0, z1, z2, h) = karatsuba_parts(&self.0, &other.0);
+        let mut out = [R::zero(); N];
+        for (shift, part) in [(0, &z0), (h, &z1), (2 * h, &z2)] {
+            for (i, c) in part.iter().enumerate() {
+                let j = shift + i;
+                if j < N {
+                    out[j] += *c;
+                } else {
+                    out[j - N] -= *c;
+                }
+            }
+        }
+        Poly(out)
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> Poly<R, N> {
+    /// The matrix of multiplication by self in the basis 1, x, ..., x^(N-1): column j
+    /// holds the coefficients of self * x^j, so entry (i, j) is a_(i - j) for i >= j and
+    /// -a_(N + i - j) otherwise. Multiplying it by the coefficient vector of b gives those
+    /// of self * b.
+    pub fn to_negacyclic_matrix(&self) -> Matrix<R, N, N> {
+        Matrix(array::from_fn(|i| {
+            array::from_fn(|j| {
+                if i >= j {
+                    self.0[i - j]
+                } else {
+                    -self.0[N + i - j]
+                }
+            })
+        }))
+    }
+
+    /// p(m) for the polynomial a_0 + a_1 x + ... + a_(N-1) x^(N-1) of self's coefficients,
+    /// by Horner's method: N matrix products, with each a_i entering as the scalar
+    /// matrix a_i * 1. This evaluates the representative in R[x], so it respects
+    /// multiplication in R[x]/(x^N + 1) only if m^N = -1.
+    pub fn eval_at_matrix<const D: usize>(&self, m: &Matrix<R, D, D>) -> Matrix<R, D, D> {
+        self.0
+            .iter()
+            .rev()
+            .fold(Matrix::zero(), |acc, c| acc * *m + Matrix::from(*c))
+    }
+
+    /// Ring switching down from R_N = R[x]/(x^N + 1) to R_M = R[y]/(y^M + 1) for N = 2M,
+    /// by the trace. R_N is R_M[x]/(x^2 - y) with y = x^2, and its automorphism x -> -x
+    /// fixes exactly R_M, so Tr(a) = a(x) + a(-x) = 2 sum_j a_(2j) y^j: twice the even
+    /// coefficients. The trace is R_M-linear, Tr(up(b) a) = b Tr(a), and undoes
+    /// ring_switch_up up to that factor, Tr(up(b)) = 2b. Panics unless N = 2M.
+    pub fn ring_switch_down<const M: usize>(&self) -> Poly<R, M> {
+        assert_eq!(N, 2 * M, "ring switching needs N = 2M");
+        Poly(array::from_fn(|j| self.0[2 * j] + self.0[2 * j]))
+    }
+
+    /// Ring switching up from R_N to R_M = R[x]/(x^M + 1) for M = 2N, by the inclusion
+    /// y -> x^2 (a ring homomorphism, as y^N + 1 maps to x^M + 1): coefficient j moves to
+    /// x^(2j), with the odd coefficients zero. Panics unless M = 2N.
+    pub fn ring_switch_up<const M: usize>(&self) -> Poly<R, M> {
+        assert_eq!(M, 2 * N, "ring switching needs M = 2N");
+        Poly(array::from_fn(|i| {
+            if i % 2 == 0 {
+                self.0[i / 2]
+            } else {
+                R::zero()
+            }
+        }))
+    }
+
+    /// The Gram matrix M^T M of the negacyclic matrix M of self, whose (j, k) entry is the
+    /// inner product of the coefficient vectors of self * x^j and self * x^k, i.e. the
+    /// geometry of the ideal lattice self generates (for security estimates). Over Z/qZ
+    /// this is that matrix mod q; lift to centered representatives first (map_coeffs) if
+    /// the integer Gram matrix is wanted.
+    pub fn gram_matrix(&self) -> Matrix<R, N, N> {
+        let m = self.to_negacyclic_matrix();
+        m.transpose() * m
+    }
+}
+
+macro_rules! op_assign {
+    ($func:ident, $bound:ident, $method:ident) => {
+        impl<R: RingCommutative + Copy, const N: usize> $bound<Poly<R, N>> for Poly<R, N> {
+            fn $func(&mut self, other: Self) {
+                *self = self.$method(other)
+            }
+        }
+    };
+}
+op_assign!(add_assign, AddAssign, add);
+op_assign!(mul_assign, MulAssign, mul);
+op_assign!(sub_assign, SubAssign, sub);
+
+impl<R: RingCommutative + Copy, const N: usize> Zero for Poly<R, N> {
+    fn zero() -> Self {
+        Poly([R::zero(); N])
+    }
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(|c| c.is_zero())
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> One for Poly<R, N> {
+    fn one() -> Self {
+        let mut out = Self::zero();
+        // For N = 0 this is the zero ring, where 1 = 0
+        if N > 0 {
+            out.0[0] = R::one();
+        }
+        out
+    }
+    fn is_one(&self) -> bool
+    where
+        Self: PartialEq,
+    {
+        self.0
+            .iter()
+            .enumerate()
+            .all(|(i, c)| if i == 0 { c.is_one() } else { c.is_zero() })
+    }
+}
+
+impl<R: RingElement, const N: usize> Poly<R, N> {
+    /// Raises each coefficient to the power e independently (not the ring power, which is
+    /// RingElement::pow), e.g. for power sums of slot values.
+    pub fn coeff_pow(&self, e: u64) -> Self {
+        Poly(self.0.map(|c| c.pow(e)))
+    }
+
+    /// The isomorphism from the cyclic ring R[x]/(x^N - 1) to the negacyclic one given by
+    /// an element psi with psi^N = -1 (a primitive 2N-th root of unity, for Z/qZ): the
+    /// cyclic element with coefficients c maps to sum_i c_i psi^i x^i, i.e. x to psi x.
+    /// Substituting psi x for x sends x^N - 1 to -(x^N + 1), so products are preserved,
+    /// and this is how the negacyclic NTT reduces to a cyclic one. Panics unless
+    /// psi^N = -1.
+    pub fn twist(cyclic: &[R; N], psi: R) -> Self {
+        Self::check_twist_root(psi);
+        let mut power = R::one();
+        Poly(cyclic.map(|c| {
+            let out = c * power;
+            power *= psi;
+            out
+        }))
+    }
+
+    /// Inverse of twist, back to the coefficients in R[x]/(x^N - 1): coefficient i is
+    /// multiplied by psi^-i = psi^(2N - i). Panics unless psi^N = -1.
+    pub fn untwist(&self, psi: R) -> [R; N] {
+        Self::check_twist_root(psi);
+        let psi_inv = psi.pow(2 * N as u64 - 1);
+        let mut power = R::one();
+        self.0.map(|c| {
+            let out = c * power;
+            power *= psi_inv;
+            out
+        })
+    }
+
+    fn check_twist_root(psi: R) {
+        assert!(
+            (psi.pow(N as u64) + R::one()).is_zero(),
+            "twisting needs psi^N = -1"
+        );
+    }
+}
+
+impl<R: Zero + Copy, const N: usize> Poly<R, N> {
+    /// The same coefficients in M slots: zero-extended if M > N, or truncated (dropping
+    /// the coefficients of x^M and up) if M < N. Truncation is lossy, and neither
+    /// direction is a ring homomorphism in general, as x^N + 1 and x^M + 1 differ.
+    pub fn resize<const M: usize>(self) -> Poly<R, M> {
+        Poly(array::from_fn(
+            |i| if i < N { self.0[i] } else { R::zero() },
+        ))
+    }
+}
+
+impl<R: Zero + Copy, const N: usize> Default for Poly<R, N> {
+    fn default() -> Self {
+        Poly([R::zero(); N])
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> Poly<R, N> {
+    /// c * self, as a new polynomial
+    pub fn scale(&self, c: R) -> Self {
+        Poly(self.0.map(|x| x * c))
+    }
+
+    /// Multiplies every coefficient by c, without building a new polynomial
+    pub fn scale_in_place(&mut self, c: R) {
+        for x in self.0.iter_mut() {
+            *x *= c;
+        }
+    }
+}
+
+impl<R: Zero, const N: usize> Poly<R, N> {
+    /// Whether exactly `weight` coefficients are nonzero, e.g. to validate an imported
+    /// fixed-Hamming-weight ternary secret.
+    pub fn verify_fixed_weight(&self, weight: usize) -> bool {
+        self.0.iter().filter(|c| !c.is_zero()).count() == weight
+    }
+}
+
+impl<R, const N: usize> Poly<R, N> {
+    /// The coefficient array, constant term first
+    pub fn coeffs(&self) -> &[R; N] {
+        &self.0
+    }
+
+    /// The coefficient array, for filling a polynomial coefficient by coefficient
+    pub fn coeffs_mut(&mut self) -> &mut [R; N] {
+        &mut self.0
+    }
+
+    /// Applies f to every coefficient, possibly changing the coefficient ring (e.g.
+    /// switching the modulus of Z/qZ, or embedding into floats). This is a ring map only
+    /// when f is.
+    pub fn map_coeffs<S, F: Fn(R) -> S>(self, f: F) -> Poly<S, N> {
+        Poly(self.0.map(f))
+    }
+
+    /// The expansion factor of Z[x]/(x^N + 1) in the infinity norm: the least gamma with
+    /// ||a * b|| <= gamma ||a|| ||b|| for all a and b. Each product coefficient is a signed
+    /// sum of N terms a_i b_j, so gamma = N, and a = 1 + x + ... + x^(N-1) with
+    /// b = 1 - x - ... - x^(N-1) attains it.
+    pub fn expansion_factor() -> f64 {
+        N as f64
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> Identity<Additive> for Poly<R, N> {
+    fn identity() -> Self {
+        Self::zero()
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> Identity<Multiplicative> for Poly<R, N> {
+    fn identity() -> Self {
+        Self::one()
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> AbstractMagma<Additive> for Poly<R, N> {
+    fn operate(&self, other: &Self) -> Self {
+        *self + *other
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> TwoSidedInverse<Additive> for Poly<R, N> {
+    fn two_sided_inverse(&self) -> Self {
+        -*self
+    }
+}
+
+impl<R: RingCommutative + Copy, const N: usize> AbstractMagma<Multiplicative> for Poly<R, N> {
+    fn operate(&self, other: &Self) -> Self {
+        *self * *other
+    }
+}
+
+impl<const Q: u32, const N: usize> Poly<Modular<Q>, N> {
+    /// The coefficients' Modular::to_le_bytes encodings, constant term first.
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        self.0.iter().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    /// Inverse of to_le_bytes, or None if bytes is not N * Modular::<Q>::BYTES long or
+    /// any coefficient is outside [0, Q).
+    pub fn from_le_bytes(bytes: &[u8]) -> Option<Self> {
+        let width = Modular::<Q>::BYTES;
+        if bytes.len() != N * width {
+            return None;
+        }
+        let mut out = Self::zero();
+        for (c, chunk) in out.0.iter_mut().zip(bytes.chunks_exact(width)) {
+            *c = Modular::from_le_bytes(chunk)?;
+        }
+        Some(out)
+    }
+
+    /// The bit pattern of a polynomial whose coefficients are all 0 or 1, such as a binary
+    /// secret, constant term first. Panics if any coefficient is neither 0 nor 1.
+    pub fn to_bits(&self) -> Vec<bool> {
+        self.0
+            .iter()
+            .map(|c| {
+                assert!(
+                    c.is_zero() || c.is_one(),
+                    "to_bits on a non-binary polynomial"
+                );
+                c.is_one()
+            })
+            .collect()
+    }
+
+    /// Inverse of to_bits, or None if bits does not have exactly N entries.
+    pub fn from_bits(bits: &[bool]) -> Option<Self> {
+        if bits.len() != N {
+            return None;
+        }
+        let mut p = Self::zero();
+        for (c, b) in p.0.iter_mut().zip(bits) {
+            if *b {
+                *c = Modular::one();
+            }
+        }
+        Some(p)
+    }
+
+    /// Splits a byte stream into plaintext polynomials, each coefficient carrying the
+    /// floor(log2 Q) bits below it, least significant bit first. The stream is padded
+    /// with a 0x80 byte and then zero bits up to a whole polynomial (ISO/IEC 7816-4), so
+    /// unpack_bytes recovers the exact length; the padding always takes at least one byte,
+    /// and adds a polynomial when data already ends on a boundary. Panics if N = 0 or
+    /// Q < 2.
+    pub fn pack_bytes(data: &[u8]) -> impl Iterator<Item = Self> + '_ {
+        let width = Self::packed_bits();
+        assert!(N > 0, "cannot pack bytes into polynomials of degree 0");
+        let bit = move |i: usize| match data.get(i / 8) {
+            Some(byte) => u32::from(byte >> (i % 8)) & 1,
+            None => u32::from(i == 8 * data.len() + 7),
+        };
+        let per_poly = N * width;
+        let count = (8 * (data.len() + 1)).div_ceil(per_poly);
+        (0..count).map(move |k| {
+            let mut p = Self::zero();
+            for (j, c) in p.0.iter_mut().enumerate() {
+                let start = k * per_poly + j * width;
+                let x = (0..width).fold(0, |x, t| x | bit(start + t) << t);
+                *c = Modular::from([x]);
+            }
+            p
+        })
+    }
+
+    /// Inverse of pack_bytes, or None if a coefficient does not fit in floor(log2 Q) bits
+    /// or the padding is malformed.
+    pub fn unpack_bytes(polys: &[Self]) -> Option<Vec<u8>> {
+        let width = Self::packed_bits();
+        let mut bytes = Vec::with_capacity(polys.len() * N * width / 8);
+        let (mut acc, mut filled) = (0u64, 0);
+        for c in polys.iter().flat_map(|p| p.0.iter()) {
+            let x = c.rem_small(Q);
+            if x >> width != 0 {
+                return None;
+            }
+            acc |= u64::from(x) << filled;
+            filled += width;
+            while filled >= 8 {
+                bytes.push(acc as u8);
+                acc >>= 8;
+                filled -= 8;
+            }
+        }
+        // Leftover bits short of a byte are padding too
+        while bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+        match bytes.pop() {
+            Some(0x80) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    fn packed_bits() -> usize {
+        assert!(Q >= 2, "cannot pack bits into Z/{}Z", Q);
+        (31 - Q.leading_zeros()) as usize
+    }
+
+    /// The content: the gcd d of Q and the coefficients' representatives in [0, Q), as an
+    /// element of Z/QZ. Over Z/QZ the ideal the coefficients generate is (d) for this
+    /// divisor d of Q, which makes it the natural gcd; for prime Q every nonzero scalar is
+    /// a unit, so the content is 1 for any nonzero polynomial. The zero polynomial has
+    /// d = Q, i.e. content 0.
+    pub fn content(&self) -> Modular<Q> {
+        let gcd = |mut a: u32, mut b: u32| {
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            a
+        };
+        let d = self.0.iter().fold(Q, |d, c| gcd(d, c.rem_small(Q)));
+        Modular::from([d])
+    }
+
+    /// self with the content divided out, coefficient i being a_i / d for the
+    /// representatives in [0, Q), so that content() * primitive_part() = self. Over
+    /// composite Q the quotient is only determined mod Q/d (this picks the one below Q/d),
+    /// and need not have content 1: 8 has content 4 mod 12, leaving 2. The primitive part
+    /// of 0 is 0.
+    pub fn primitive_part(&self) -> Self {
+        let d = match self.content().rem_small(Q) {
+            0 => return *self,
+            d => d,
+        };
+        Poly(self.0.map(|c| Modular::from([c.rem_small(Q) / d])))
+    }
+
+    /// The minimal polynomial of self over Z/QZ, as its coefficients c_0, ..., c_d
+    /// (constant term first, monic so c_d = 1): the lowest degree d with
+    /// sum_i c_i self^i = 0. It is the first linear dependency among the coefficient
+    /// vectors of 1, self, self^2, ..., found by Gaussian elimination as they are added,
+    /// so d <= N. A degree below N shows self lies in a proper subring, e.g. x^2 in that of
+    /// the even powers. Panics unless Q is prime.
+    pub fn minimal_polynomial(&self) -> Vec<Modular<Q>> {
+        assert!(
+            crate::primes::is_prime(Q),
+            "minimal_polynomial needs a prime modulus, got {}",
+            Q
+        );
+        // Each reduced row: (pivot column, coefficient vector, combination of powers)
+        let mut rows: Vec<(usize, [Modular<Q>; N], Vec<Modular<Q>>)> = Vec::new();
+        let mut power = Self::one();
+        for k in 0..=N {
+            let mut v = power.0;
+            let mut combination = vec![Modular::zero(); k + 1];
+            combination[k] = Modular::one();
+            for (pivot, row, row_combination) in &rows {
+                let factor = v[*pivot];
+                if !factor.is_zero() {
+                    for (x, y) in v.iter_mut().zip(row) {
+                        *x -= factor * *y;
+                    }
+                    for (x, y) in combination.iter_mut().zip(row_combination) {
+                        *x -= factor * *y;
+                    }
+                }
+            }
+            match v.iter().position(|c| !c.is_zero()) {
+                None => return combination,
+                Some(pivot) => {
+                    let inv = v[pivot].inverse().unwrap();
+                    let row = v.map(|c| c * inv);
+                    let row_combination: Vec<_> = combination.iter().map(|&c| c * inv).collect();
+                    // Keep earlier rows reduced at this pivot, so later vectors can be
+                    // reduced in one pass
+                    for (_, other, other_combination) in rows.iter_mut() {
+                        let factor = other[pivot];
+                        if !factor.is_zero() {
+                            for (x, y) in other.iter_mut().zip(&row) {
+                                *x -= factor * *y;
+                            }
+                            other_combination.resize(k + 1, Modular::zero());
+                            for (x, y) in other_combination.iter_mut().zip(&row_combination) {
+                                *x -= factor * *y;
+                            }
+                        }
+                    }
+                    rows.push((pivot, row, row_combination));
+                }
+            }
+            power *= *self;
+        }
+        unreachable!("N + 1 vectors in an N-dimensional space are dependent")
+    }
+
+    /// Each coefficient's centered representative in (-Q/2, Q/2], constant term first.
+    pub fn centered_coeffs(&self) -> [i64; N] {
+        self.0.map(|c| c.centered())
+    }
+
+    /// max_i |a_i| over the centered representatives in (-Q/2, Q/2].
+    pub fn norm_inf(&self) -> u64 {
+        self.0
+            .iter()
+            .map(|c| c.centered().unsigned_abs())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// sum_i a_i^2 over the centered representatives. This overflows (panicking in debug
+    /// builds) only for N * (Q/2)^2 >= 2^64.
+    pub fn norm_l2_squared(&self) -> u64 {
+        self.0
+            .iter()
+            .map(|c| c.centered().unsigned_abs().pow(2))
+            .sum()
+    }
+
+    /// (min, max, standard deviation) of the centered coefficients, a quick check of
+    /// whether the noise in v - u * s is within budget. The standard deviation is the
+    /// population one (dividing by N). All three are 0 for N = 0.
+    pub fn noise_profile(&self) -> (i64, i64, f64) {
+        let centered = self.centered_coeffs();
+        if N == 0 {
+            return (0, 0, 0.0);
+        }
+        let min = centered.iter().copied().min().unwrap_or(0);
+        let max = centered.iter().copied().max().unwrap_or(0);
+        let n = N as f64;
+        let mean = centered.iter().map(|&c| c as f64).sum::<f64>() / n;
+        let var = centered
+            .iter()
+            .map(|&c| (c as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        (min, max, var.sqrt())
+    }
+
+    /// The identification of R_q with the dual ring R_q^v.
+    ///
+    /// For N a power of two, the codifferent of R = Z[x]/(x^N + 1) is R^v = (1/N) R, so
+    /// every element of R^v is a / N for a unique a in R. Reducing mod an odd Q (where N
+    /// is invertible), to_dual takes a to the representative of a / N, i.e. multiplies
+    /// every coefficient by N^-1 mod Q, and from_dual multiplies by N. Panics if N is not
+    /// invertible mod Q.
+    pub fn to_dual(&self) -> Self {
+        let n_inv = Modular::<Q>::from([N as u32])
+            .inverse()
+            .expect("the dimension is not invertible mod Q");
+        Poly(self.0.map(|c| c * n_inv))
+    }
+
+    /// Inverse of to_dual.
+    pub fn from_dual(&self) -> Self {
+        let n = Modular::<Q>::from([N as u32]);
+        Poly(self.0.map(|c| c * n))
+    }
+}
+
+/// The negacyclic number theoretic transform, for O(N log N) multiplication in
+/// (Z/qZ)[x]/(x^N + 1) when N is a power of two and q is a prime with q = 1 mod 2N.
+///
+/// Given a primitive 2N-th root of unity psi, x^N + 1 splits into the linear factors
+/// x - psi^(2i + 1), so by the CRT the ring is isomorphic to (Z/qZ)^N. The forward transform
+/// evaluates a polynomial at these roots (in bit-reversed order), where multiplication is
+/// pointwise.
+pub mod ntt {
+    use super::*;
+    use crate::primes::{is_ntt_friendly, is_prime};
+    use crate::rings::modular::barrett_reduce;
+    use crate::rings::RingElement;
+
+    /// The precomputed powers of a primitive 2N-th root of unity mod Q that the transform
+    /// needs. Building one searches for the root, so reuse it across transforms.
+    pub struct NttContext<const Q: u32, const N: usize> {
+        /// zetas[k] = psi^bitrev(k), where bitrev reverses the low log2(N) bits
+        zetas: [Modular<Q>; N],
+        n_inv: Modular<Q>,
+    }
+
+    impl<const Q: u32, const N: usize> NttContext<Q, N> {
+        /// Panics unless N is a power of two and Q is a prime with Q = 1 mod 2N.
+        pub fn new() -> Self {
+            assert!(
+                N.is_power_of_two(),
+                "NTT dimension {} is not a power of two",
+                N
+            );
+            assert!(
+                is_prime(Q) && is_ntt_friendly(Q, N),
+                "{} is not a prime = 1 mod {}",
+                Q,
+                2 * N
+            );
+            let psi = Self::primitive_root();
+            #[cfg(debug_assertions)]
+            crate::testing::assert_primitive_root(psi, 2 * N as u64);
+            let mut powers = [Modular::one(); N];
+            for i in 1..N {
+                powers[i] = powers[i - 1] * psi;
+            }
+            let log_n = N.trailing_zeros();
+            let bitrev = |k: usize| (k as u64).reverse_bits().checked_shr(64 - log_n);
+            NttContext {
+                zetas: array::from_fn(|k| powers[bitrev(k).unwrap_or(0) as usize]),
+                n_inv: Modular::from([N as u32]).inverse().unwrap(),
+            }
+        }
+
+        /// new as a const fn, so a context can be a const or static, built at compile
+        /// time: const CTX: NttContext<Q, N> = NttContext::new_const(). Panics (failing
+        /// the build, in a const) under the same conditions as new.
+        pub const fn new_const() -> Self {
+            NttContext {
+                zetas: twiddles::<Q, N>(),
+                // N^(Q - 2) = N^-1, by Fermat
+                n_inv: Modular::new_const(N as u32).pow_const(Q as u64 - 2),
+            }
+        }
+
+        /// A primitive 2N-th root of unity psi. For g ranging over Z/QZ, g^((Q - 1)/2N)
+        /// has order dividing 2N, and exactly 2N iff its N-th power is -1.
+        fn primitive_root() -> Modular<Q> {
+            let exp = u64::from(Q - 1) / (2 * N as u64);
+            (2..Q)
+                .map(|g| Modular::<Q>::from([g]).pow(exp))
+                .find(|psi| psi.pow(N as u64) == -Modular::one())
+                .unwrap()
+        }
+
+        /// The twiddle factors psi^bitrev(k) that ntt_stage takes.
+        pub fn twiddles(&self) -> &[Modular<Q>; N] {
+            &self.zetas
+        }
+
+        /// The forward transform (Cooley-Tukey butterflies), in place: the log2(N) layers
+        /// of ntt_stage in order.
+        pub fn forward(&self, a: &mut Poly<Modular<Q>, N>) {
+            for stage in 0..N.trailing_zeros() as usize {
+                ntt_stage(&mut a.0, stage, &self.zetas);
+            }
+        }
+
+        /// The inverse transform (Gentleman-Sande butterflies), in place.
+        pub fn inverse(&self, a: &mut Poly<Modular<Q>, N>) {
+            let a = &mut a.0;
+            let mut k = N;
+            let mut len = 1;
+            while len < N {
+                for start in (0..N).step_by(2 * len) {
+                    k -= 1;
+                    let zeta = -self.zetas[k];
+                    for j in start..start + len {
+                        let t = a[j];
+                        a[j] = t + a[j + len];
+                        a[j + len] = zeta * (t - a[j + len]);
+                    }
+                }
+                len *= 2;
+            }
+            for c in a.iter_mut() {
+                *c *= self.n_inv;
+            }
+        }
+
+        /// The product of two polynomials, via transforming, pointwise multiplying, and
+        /// transforming back.
+        pub fn mul(&self, a: &Poly<Modular<Q>, N>, b: &Poly<Modular<Q>, N>) -> Poly<Modular<Q>, N> {
+            let (mut a, mut b) = (*a, *b);
+            self.forward(&mut a);
+            self.forward(&mut b);
+            let mut c = Poly(array::from_fn(|i| a.0[i] * b.0[i]));
+            self.inverse(&mut c);
+            c
+        }
+    }
+
+    /// A negacyclic NTT for dimensions N = 2^k 3^l (such as 12 or 768), whose odd factor
+    /// rules out the radix-2 NttContext. As there, Q must be a prime with Q = 1 mod 2N, so
+    /// that a primitive 2N-th root of unity psi exists; the forward transform evaluates at
+    /// psi^(2i + 1) for i = 0, ..., N - 1, in natural order. It twists by psi (see
+    /// Poly::twist) and then runs a recursive decimation-in-time cyclic transform of order
+    /// N with omega = psi^2, splitting by 2 while N is even and by 3 after that.
+    pub struct MixedRadixNtt<const Q: u32, const N: usize> {
+        psi: Modular<Q>,
+        n_inv: Modular<Q>,
+    }
+
+    impl<const Q: u32, const N: usize> MixedRadixNtt<Q, N> {
+        /// Panics unless N = 2^k 3^l and Q is a prime with Q = 1 mod 2N.
+        pub fn new() -> Self {
+            let odd = N >> N.trailing_zeros();
+            assert!(
+                N > 0 && is_power_of_three(odd),
+                "NTT dimension {} is not of the form 2^k 3^l",
+                N
+            );
+            assert!(
+                is_prime(Q) && is_ntt_friendly(Q, N),
+                "{} is not a prime = 1 mod {}",
+                Q,
+                2 * N
+            );
+            // g^((Q - 1)/2N) has order dividing 2N; it is exactly 2N iff its N-th power is
+            // -1 and, when 3 divides N, its (2N/3)-th power is not 1
+            let exp = u64::from(Q - 1) / (2 * N as u64);
+            let psi = (2..Q)
+                .map(|g| Modular::<Q>::from([g]).pow(exp))
+                .find(|psi| {
+                    psi.pow(N as u64) == -Modular::one()
+                        && (!N.is_multiple_of(3) || !psi.pow(2 * N as u64 / 3).is_one())
+                })
+                .unwrap();
+            #[cfg(debug_assertions)]
+            crate::testing::assert_primitive_root(psi, 2 * N as u64);
+            MixedRadixNtt {
+                psi,
+                n_inv: Modular::from([N as u32]).inverse().unwrap(),
+            }
+        }
+
+        /// The forward transform, a(psi^(2i + 1)) into slot i.
+        pub fn forward(&self, a: &Poly<Modular<Q>, N>) -> [Modular<Q>; N] {
+            let twisted = Poly::twist(&a.0, self.psi);
+            let slots = cyclic_dft(&twisted.0, self.psi * self.psi);
+            array::from_fn(|i| slots[i])
+        }
+
+        /// The inverse transform.
+        pub fn inverse(&self, slots: &[Modular<Q>; N]) -> Poly<Modular<Q>, N> {
+            let omega_inv = self.psi.pow(2 * N as u64 - 2);
+            let cyclic = cyclic_dft(slots, omega_inv);
+            Poly(Poly(array::from_fn(|i| cyclic[i] * self.n_inv)).untwist(self.psi))
+        }
+
+        /// The product of two polynomials, via transforming, pointwise multiplying, and
+        /// transforming back.
+        pub fn mul(&self, a: &Poly<Modular<Q>, N>, b: &Poly<Modular<Q>, N>) -> Poly<Modular<Q>, N> {
+            let (a, b) = (self.forward(a), self.forward(b));
+            self.inverse(&array::from_fn(|i| a[i] * b[i]))
+        }
+    }
+
+    fn is_power_of_three(mut n: usize) -> bool {
+        while n > 1 && n.is_multiple_of(3) {
+            n /= 3;
+        }
+        n == 1
+    }
+
+    /// sum_j x_j omega^(jk) for each k, for omega of order x.len() = 2^k 3^l, splitting
+    /// x into its residue classes mod 2 (or 3) and combining their half (or third) size
+    /// transforms with the twiddles omega^(rk).
+    fn cyclic_dft<const Q: u32>(x: &[Modular<Q>], omega: Modular<Q>) -> Vec<Modular<Q>> {
+        let n = x.len();
+        if n <= 1 {
+            return x.to_vec();
+        }
+        let radix = if n.is_multiple_of(2) { 2 } else { 3 };
+        let sub_omega = omega.pow(radix as u64);
+        let parts: Vec<Vec<Modular<Q>>> = (0..radix)
+            .map(|r| {
+                let class: Vec<_> = x.iter().skip(r).step_by(radix).copied().collect();
+                cyclic_dft(&class, sub_omega)
+            })
+            .collect();
+        let m = n / radix;
+        let mut twiddle = Modular::one();
+        (0..n)
+            .map(|k| {
+                // sum_r omega^(rk) parts[r][k mod m]
+                let mut power = Modular::one();
+                let mut sum = Modular::zero();
+                for part in &parts {
+                    sum += power * part[k % m];
+                    power *= twiddle;
+                }
+                twiddle *= omega;
+                sum
+            })
+            .collect()
+    }
+
+    impl<const Q: u32, const N: usize> Default for MixedRadixNtt<Q, N> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    /// The twiddle table of NttContext::<Q, N>::new() (see NttContext::twiddles) as a const
+    /// fn, finding the same primitive 2N-th root psi and listing psi^bitrev(k). Panics
+    /// unless N is a power of two and Q is a prime with Q = 1 mod 2N.
+    pub const fn twiddles<const Q: u32, const N: usize>() -> [Modular<Q>; N] {
+        assert!(N.is_power_of_two(), "NTT dimension is not a power of two");
+        assert!(
+            is_prime(Q) && is_ntt_friendly(Q, N),
+            "the modulus is not a prime = 1 mod 2N"
+        );
+        let exp = (Q as u64 - 1) / (2 * N as u64);
+        let mut g = 2;
+        let psi = loop {
+            let psi = Modular::<Q>::new_const(g).pow_const(exp);
+            if psi.pow_const(N as u64).val() == Q - 1 {
+                break psi;
+            }
+            g += 1;
+        };
+        let log_n = N.trailing_zeros();
+        let mut out = [Modular::new_const(0); N];
+        let mut k = 0;
+        while k < N {
+            let rev = if log_n == 0 {
+                0
+            } else {
+                (k as u64).reverse_bits() >> (64 - log_n)
+            };
+            out[k] = psi.pow_const(rev);
+            k += 1;
+        }
+        out
+    }
+
+    /// Layer `stage` (from 0) of the forward transform, in place: 2^stage blocks of
+    /// Cooley-Tukey butterflies at distance N / 2^(stage + 1), block i using twiddle
+    /// twiddles[2^stage + i]. Running stages 0, 1, ..., log2(N) - 1 with
+    /// NttContext::twiddles is NttContext::forward, so custom kernels can be interleaved
+    /// between layers. Panics unless data.len() is a power of two, stage < log2(len) and
+    /// twiddles covers it.
+    pub fn ntt_stage<const Q: u32>(data: &mut [Modular<Q>], stage: usize, twiddles: &[Modular<Q>]) {
+        let n = data.len();
+        assert!(
+            n.is_power_of_two(),
+            "NTT length {} is not a power of two",
+            n
+        );
+        assert!(
+            stage < n.trailing_zeros() as usize,
+            "stage {} is out of range for length {}",
+            stage,
+            n
+        );
+        assert!(
+            twiddles.len() >= n,
+            "need {} twiddles, got {}",
+            n,
+            twiddles.len()
+        );
+        let len = n >> (stage + 1);
+        for (i, start) in (0..n).step_by(2 * len).enumerate() {
+            let zeta = twiddles[(1 << stage) + i];
+            for j in start..start + len {
+                let t = zeta * data[j + len];
+                data[j + len] = data[j] - t;
+                data[j] += t;
+            }
+        }
+    }
+
+    /// acc[i] += a[i] * b[i], reducing eagerly: once per slot per call (see
+    /// Modular::mul_add), as a reduced Modular accumulator has no room for unreduced
+    /// sums. For a whole module inner product prefer pointwise_mul_acc or
+    /// lazy_inner_product, which defer reduction to once per slot rather than once per
+    /// term. Panics if the slices' lengths differ.
+    pub fn pointwise_mul_acc_eager<const Q: u32>(
+        acc: &mut [Modular<Q>],
+        a: &[Modular<Q>],
+        b: &[Modular<Q>],
+    ) {
+        assert!(
+            acc.len() == a.len() && a.len() == b.len(),
+            "length mismatch"
+        );
+        for ((c, x), y) in acc.iter_mut().zip(a).zip(b) {
+            *c = c.mul_add(*x, *y);
+        }
+    }
+
+    /// How many products of reduced values a u64 accumulator slot of pointwise_mul_acc
+    /// holds without overflowing: floor((2^64 - 1) / (Q - 1)^2). This is 1 for Q near
+    /// 2^32, and about 1.7 * 10^12 for Kyber's 3329.
+    pub const fn lazy_capacity<const Q: u32>() -> u64 {
+        let max = Q as u64 - 1;
+        if max == 0 {
+            u64::MAX
+        } else {
+            u64::MAX / (max * max)
+        }
+    }
+
+    /// acc[i] += a[i] * b[i] on unreduced u64 sums, for accumulating a module inner
+    /// product term by term: start from zeroed slots, add up to lazy_capacity::<Q>()
+    /// products, then reduce_lazy each slot once, with a single Barrett reduction. More
+    /// products overflow (panicking in debug builds). Panics if the slices' lengths
+    /// differ.
+    pub fn pointwise_mul_acc<const Q: u32>(acc: &mut [u64], a: &[Modular<Q>], b: &[Modular<Q>]) {
+        assert!(
+            acc.len() == a.len() && a.len() == b.len(),
+            "length mismatch"
+        );
+        for ((c, x), y) in acc.iter_mut().zip(a).zip(b) {
+            *c += u64::from(x.to_coeffs()[0]) * u64::from(y.to_coeffs()[0]);
+        }
+    }
+
+    /// Reduces the sums of pointwise_mul_acc into out. Panics if the lengths differ.
+    pub fn reduce_lazy<const Q: u32>(acc: &[u64], out: &mut [Modular<Q>]) {
+        assert_eq!(acc.len(), out.len(), "length mismatch");
+        for (c, sum) in out.iter_mut().zip(acc) {
+            *c = barrett_reduce(*sum);
+        }
+    }
+
+    /// sum_k a[k] * b[k] for NTT-domain polynomials (so products are pointwise), as in
+    /// the <a, s> of module-LWE. Each slot accumulates its K unreduced products in a u128,
+    /// which cannot overflow for K < 2^64, and is reduced once at the end. Panics if a and
+    /// b have different lengths.
+    pub fn lazy_inner_product<const Q: u32, const N: usize>(
+        a: &[Poly<Modular<Q>, N>],
+        b: &[Poly<Modular<Q>, N>],
+    ) -> Poly<Modular<Q>, N> {
+        assert_eq!(a.len(), b.len(), "length mismatch");
+        let mut sums = [0u128; N];
+        for (x, y) in a.iter().zip(b) {
+            for (i, sum) in sums.iter_mut().enumerate() {
+                let product = u64::from(x.0[i].to_coeffs()[0]) * u64::from(y.0[i].to_coeffs()[0]);
+                *sum += u128::from(product);
+            }
+        }
+        Poly(sums.map(|sum| Modular::from([(sum % u128::from(Q)) as u32])))
+    }
+
+    impl<const Q: u32, const N: usize> Default for NttContext<Q, N> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl<const Q: u32, const N: usize> Poly<Modular<Q>, N> {
+        /// The forward NTT, in place. This builds an NttContext each call; hold on to one
+        /// instead when transforming repeatedly.
+        pub fn ntt(&mut self) {
+            NttContext::new().forward(self)
+        }
+
+        /// The inverse NTT, in place.
+        pub fn intt(&mut self) {
+            NttContext::new().inverse(self)
+        }
+
+        /// Multiplication through the NTT. Agrees with the schoolbook Mul.
+        pub fn mul_ntt(&self, other: &Self) -> Self {
+            NttContext::new().mul(self, other)
+        }
+
+        /// a * self + b for self, a and b all in the NTT domain, where the product is
+        /// pointwise: one Modular::mul_add (a single reduction) per slot. The result is in
+        /// the NTT domain too.
+        pub fn affine_ntt(&self, a: &Self, b: &Self) -> Self {
+            Poly(array::from_fn(|i| b.0[i].mul_add(a.0[i], self.0[i])))
+        }
+
+        /// The image of self under the isomorphism R_q = (Z/QZ)^N, i.e. its evaluations at
+        /// the roots of x^N + 1 (in the same order as ntt).
+        pub fn to_slots(&self) -> [Modular<Q>; N] {
+            let mut a = *self;
+            a.ntt();
+            a.0
+        }
+
+        /// The polynomial with the given slot values, the inverse of to_slots. Useful for
+        /// crafting inputs with a known multiplicative structure, e.g. zero divisors.
+        pub fn from_spectrum(slots: [Modular<Q>; N]) -> Self {
+            let mut a = Poly(slots);
+            a.intt();
+            a
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        const Q: u32 = 12289;
+        // xorshift64, so the cross-checks see many different inputs
+        fn pseudorandom_poly<const N: usize>(state: &mut u64) -> Poly<Modular<Q>, N> {
+            Poly(array::from_fn(|_| {
+                *state ^= *state << 13;
+                *state ^= *state >> 7;
+                *state ^= *state << 17;
+                Modular::from([(*state % u64::from(Q)) as u32])
+            }))
+        }
+        #[test]
+        fn test_ntt_round_trip() {
+            let mut state = 0x2545F4914F6CDD1D;
+            for _ in 0..20 {
+                let p = pseudorandom_poly::<256>(&mut state);
+                let mut q = p;
+                q.ntt();
+                assert_ne!(q, p);
+                q.intt();
+                assert_eq!(q, p);
+            }
+        }
+        #[test]
+        fn test_mul_ntt_matches_schoolbook() {
+            let mut state = 0x9E3779B97F4A7C15;
+            let ctx = NttContext::<Q, 8>::new();
+            for _ in 0..100 {
+                let a = pseudorandom_poly::<8>(&mut state);
+                let b = pseudorandom_poly::<8>(&mut state);
+                assert_eq!(ctx.mul(&a, &b), a * b);
+                assert_eq!(a.mul_ntt(&b), a * b);
+            }
+            let a = pseudorandom_poly::<512>(&mut state);
+            let b = pseudorandom_poly::<512>(&mut state);
+            assert_eq!(a.mul_ntt(&b), a * b);
+        }
+        #[test]
+        fn test_affine_ntt() {
+            let mut state = 0x5851F42D4C957F2D;
+            let x: Poly<Modular<Q>, 16> = pseudorandom_poly(&mut state);
+            let a = pseudorandom_poly(&mut state);
+            let b = pseudorandom_poly(&mut state);
+            let ctx = NttContext::new();
+            let (mut x_hat, mut a_hat, mut b_hat) = (x, a, b);
+            ctx.forward(&mut x_hat);
+            ctx.forward(&mut a_hat);
+            ctx.forward(&mut b_hat);
+            let mut y = x_hat.affine_ntt(&a_hat, &b_hat);
+            ctx.inverse(&mut y);
+            assert_eq!(y, a * x + b);
+            assert_eq!(y, x.affine(&a, &b));
+        }
+        #[test]
+        fn test_ntt_stages() {
+            let mut state = 0x14057B7EF767814F;
+            let p: Poly<Modular<Q>, 16> = pseudorandom_poly(&mut state);
+            let ctx = NttContext::new();
+            let mut expected = p;
+            ctx.forward(&mut expected);
+            let mut staged = p;
+            for stage in 0..4 {
+                ntt_stage(&mut staged.0, stage, ctx.twiddles());
+            }
+            assert_eq!(staged, expected);
+            ctx.inverse(&mut staged);
+            assert_eq!(staged, p);
+        }
+        #[test]
+        #[should_panic(expected = "stage 4 is out of range for length 16")]
+        fn test_ntt_stage_out_of_range() {
+            let ctx = NttContext::<Q, 16>::new();
+            ntt_stage(&mut [Modular::zero(); 16], 4, ctx.twiddles());
+        }
+        #[test]
+        fn test_ntt_of_constants() {
+            // Evaluating at any point leaves constants unchanged, and x^N = -1 holds
+            // through the NTT multiplication too
+            let mut one = Poly::<Modular<Q>, 8>::one();
+            one.ntt();
+            assert_eq!(one, Poly([Modular::one(); 8]));
+            let x = Poly::<Modular<Q>, 8>::from([0, 1, 0, 0, 0, 0, 0, 0]);
+            let x_4 = x.mul_ntt(&x).mul_ntt(&x.mul_ntt(&x));
+            assert_eq!(x_4.mul_ntt(&x_4), -Poly::one());
+        }
+        #[test]
+        fn test_from_spectrum() {
+            let mut state = 0x853C49E6748FEA9B;
+            let s = pseudorandom_poly::<16>(&mut state).0;
+            assert_eq!(Poly::from_spectrum(s).to_slots(), s);
+            assert_eq!(
+                Poly::<Modular<Q>, 16>::from_spectrum([Modular::one(); 16]),
+                Poly::one()
+            );
+            // A polynomial vanishing in one slot is a zero divisor
+            let mut t = [Modular::<Q>::one(); 16];
+            t[3] = Modular::zero();
+            let mut u = [Modular::<Q>::zero(); 16];
+            u[3] = Modular::one();
+            assert!((Poly::from_spectrum(t) * Poly::from_spectrum(u)).is_zero());
+        }
+        #[test]
+        fn test_lazy_inner_product() {
+            let mut state = 0x2545F4914F6CDD1D;
+            let a: Vec<Poly<Modular<Q>, 16>> =
+                (0..4).map(|_| pseudorandom_poly(&mut state)).collect();
+            let b: Vec<Poly<Modular<Q>, 16>> =
+                (0..4).map(|_| pseudorandom_poly(&mut state)).collect();
+            let mut eager = Poly::<Modular<Q>, 16>::zero();
+            for (x, y) in a.iter().zip(&b) {
+                eager += Poly(array::from_fn(|i| x.0[i] * y.0[i]));
+            }
+            assert_eq!(lazy_inner_product(&a, &b), eager);
+            let mut acc = Poly::<Modular<Q>, 16>::zero();
+            for (x, y) in a.iter().zip(&b) {
+                pointwise_mul_acc_eager(&mut acc.0, &x.0, &y.0);
+            }
+            assert_eq!(acc, eager);
+            // Near-2^32 moduli, where even one product needs more than 32 bits
+            let top = [-Modular::<4294967291>::one(); 2];
+            let big = [Poly(top); 8];
+            assert_eq!(
+                lazy_inner_product(&big, &big),
+                Poly([Modular::from([8]); 2])
+            );
+            assert!(lazy_inner_product::<Q, 4>(&[], &[]).is_zero());
+        }
+        #[test]
+        fn test_pointwise_mul_acc() {
+            let mut state = 0x9E3779B97F4A7C15;
+            let a: Vec<Poly<Modular<Q>, 16>> =
+                (0..4).map(|_| pseudorandom_poly(&mut state)).collect();
+            let b: Vec<Poly<Modular<Q>, 16>> =
+                (0..4).map(|_| pseudorandom_poly(&mut state)).collect();
+            let (mut sums, mut eager) = ([0u64; 16], Poly::<Modular<Q>, 16>::zero());
+            for (x, y) in a.iter().zip(&b) {
+                pointwise_mul_acc(&mut sums, &x.0, &y.0);
+                pointwise_mul_acc_eager(&mut eager.0, &x.0, &y.0);
+            }
+            let mut lazy = Poly::<Modular<Q>, 16>::zero();
+            reduce_lazy(&sums, &mut lazy.0);
+            assert_eq!(lazy, eager);
+            assert_eq!(lazy, lazy_inner_product(&a, &b));
+        }
+        #[test]
+        fn test_pointwise_mul_acc_max_values() {
+            // Filling a slot to capacity with (Q - 1)^2 products, which for a modulus near
+            // 2^32 is a single one
+            fn fill<const Q: u32>() {
+                let max = [-Modular::<Q>::one(); 3];
+                let (mut sums, mut eager) = ([0u64; 3], [Modular::<Q>::zero(); 3]);
+                let terms = lazy_capacity::<Q>().min(1000);
+                // Stand in for the terms not added one by one
+                sums[0] = (lazy_capacity::<Q>() - terms) * u64::from(Q - 1).pow(2);
+                eager[0] = Modular::from_i128(i128::from(sums[0]));
+                for _ in 0..terms {
+                    pointwise_mul_acc(&mut sums, &max, &max);
+                    pointwise_mul_acc_eager(&mut eager, &max, &max);
+                }
+                let mut lazy = [Modular::<Q>::zero(); 3];
+                reduce_lazy(&sums, &mut lazy);
+                assert_eq!(lazy, eager);
+                assert!(sums[0].checked_add(u64::from(Q - 1).pow(2)).is_none());
+            }
+            assert_eq!(lazy_capacity::<4294967291>(), 1);
+            fill::<4294967291>();
+            fill::<3329>();
+            fill::<Q>();
+            assert_eq!(lazy_capacity::<1>(), u64::MAX);
+        }
+        #[test]
+        fn test_const_twiddles() {
+            const TWIDDLES: [Modular<Q>; 16] = twiddles::<Q, 16>();
+            const CTX: NttContext<Q, 16> = NttContext::new_const();
+            // psi^0 first, then psi^bitrev(15) = psi^15 last
+            let psi = TWIDDLES[8];
+            assert!(TWIDDLES[0].is_one());
+            assert_eq!(TWIDDLES[15], psi.pow(15));
+            assert_eq!(psi.pow(16), -Modular::one());
+            let ctx = NttContext::<Q, 16>::new();
+            assert_eq!(&TWIDDLES, ctx.twiddles());
+            assert_eq!(CTX.twiddles(), ctx.twiddles());
+            assert_eq!(CTX.n_inv, ctx.n_inv);
+            let mut state = 11;
+            let (a, b) = (pseudorandom_poly(&mut state), pseudorandom_poly(&mut state));
+            assert_eq!(CTX.mul(&a, &b), a * b);
+            const ONE: [Modular<7681>; 1] = twiddles::<7681, 1>();
+            assert!(ONE[0].is_one());
+        }
+        #[test]
+        fn test_mixed_radix() {
+            // 12289 - 1 = 2^12 * 3, so every N = 2^k 3 up to 6144 is supported
+            fn check<const N: usize>(state: &mut u64) {
+                let ctx = MixedRadixNtt::<Q, N>::new();
+                let a: Poly<Modular<Q>, N> = Poly(array::from_fn(|_| {
+                    *state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                    Modular::from([(*state >> 33) as u32])
+                }));
+                let b: Poly<Modular<Q>, N> =
+                    Poly(array::from_fn(|i| Modular::from([i as u32 * 7 + 1])));
+                assert_eq!(ctx.inverse(&ctx.forward(&a)), a);
+                assert_eq!(ctx.mul(&a, &b), a * b);
+            }
+            let mut state = 7;
+            check::<12>(&mut state);
+            check::<3>(&mut state);
+            check::<24>(&mut state);
+            check::<48>(&mut state);
+            check::<1>(&mut state);
+            check::<16>(&mut state);
+            // Slot i is the evaluation at psi^(2i + 1), so x maps to those roots
+            let ctx = MixedRadixNtt::<Q, 12>::new();
+            let mut x = Poly::<Modular<Q>, 12>::zero();
+            x.0[1] = Modular::one();
+            let slots = ctx.forward(&x);
+            for (i, slot) in slots.iter().enumerate() {
+                assert_eq!(*slot, ctx.psi.pow(2 * i as u64 + 1));
+                assert_eq!(slot.pow(12), -Modular::one());
+            }
+        }
+        #[test]
+        #[should_panic(expected = "NTT dimension 10 is not of the form 2^k 3^l")]
+        fn test_mixed_radix_unsupported() {
+            MixedRadixNtt::<Q, 10>::new();
+        }
+        #[test]
+        #[should_panic(expected = "3329 is not a prime = 1 mod 24")]
+        fn test_mixed_radix_unfriendly_modulus() {
+            // 3329 - 1 = 2^8 * 13
+            MixedRadixNtt::<3329, 12>::new();
+        }
+        #[test]
+        #[should_panic(expected = "3329 is not a prime = 1 mod 512")]
+        fn test_ntt_unfriendly_modulus() {
+            NttContext::<3329, 256>::new();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const Q: u32 = 12289;
+    type P = Poly<Modular<Q>, 4>;
+    #[test]
+    fn test_from_u32s() {
+        let p = P::from([1, Q + 2, 2 * Q, 4]);
+        assert_eq!(p, Poly([1, 2, 0, 4].map(|c| Modular::from([c]))));
+        assert_eq!(p.to_coeffs(), [1, 2, 0, 4]);
+    }
+    #[test]
+    fn test_from_base() {
+        let c = Modular::from([7]);
+        assert_eq!(P::from_base(c), P::from([7, 0, 0, 0]));
+        assert_eq!(P::from_base(Modular::one()), P::one());
+        // The inclusion is a ring map: it agrees with scaling coefficient-wise
+        let p = P::from([1, 2, 3, 4]);
+        assert_eq!(P::from_base(c) * p, Poly(p.0.map(|x| c * x)));
+        assert_eq!(P::from_base(c) + P::from_base(c), P::from_base(c + c));
+    }
+    #[test]
+    fn test_coeffs() {
+        let mut p = P::from([1, 2, 3, 4]);
+        assert_eq!(p.coeffs()[2], Modular::from([3]));
+        p.coeffs_mut()[2] = Modular::from([9]);
+        assert_eq!(p, P::from([1, 2, 9, 4]));
+        assert_eq!(p.coeffs(), &[1, 2, 9, 4].map(|c| Modular::from([c])));
+    }
+    #[test]
+    fn test_map_coeffs() {
+        let p = P::from([1, 2, Q - 3, 0]);
+        assert_eq!(p.map_coeffs(|c| -c), -p);
+        // Modulus switching Q -> 17 by rounding c * 17/Q, on centered representatives
+        let switch = |c: Modular<Q>| {
+            let scaled = (c.centered() as f64 * 17.0 / Q as f64).round() as i64;
+            Modular::<17>::from(scaled)
+        };
+        let big = P::from([Q / 2, 0, Q - 1, 723]);
+        assert_eq!(
+            big.map_coeffs(switch),
+            Poly::<Modular<17>, 4>::from([8, 0, 0, 1])
+        );
+        assert_eq!(p.map_coeffs(|c| c.centered()).0, [1, 2, -3, 0]);
+    }
+    #[test]
+    fn test_resize() {
+        let p = P::from([1, 2, 3, 4]);
+        let extended: Poly<Modular<Q>, 8> = p.resize();
+        assert_eq!(extended, Poly::from([1, 2, 3, 4, 0, 0, 0, 0]));
+        assert_eq!(extended.resize::<4>(), p);
+        assert_eq!(p.resize::<2>(), Poly::from([1, 2]));
+        assert_eq!(p.resize::<4>(), p);
+    }
+    #[test]
+    fn test_add_sub() {
+        let p = P::from([1, 2, 3, 4]);
+        let q = P::from([Q - 1, 5, 0, 7]);
+        assert_eq!(p + q, P::from([0, 7, 3, 11]));
+        assert_eq!(p + q - q, p);
+        assert_eq!(p - p, P::zero());
+        assert_eq!(-p + p, P::zero());
+    }
+    #[test]
+    fn test_default() {
+        assert_eq!(P::default(), P::zero());
+        assert_eq!(P::default(), Poly([Modular::zero(); 4]));
+    }
+    #[test]
+    fn test_is_zero_is_one() {
+        assert!(P::zero().is_zero());
+        assert!(P::one().is_one());
+        assert!(!P::zero().is_one());
+        assert!(!P::one().is_zero());
+        // 1 + x is neither, nor is x
+        assert!(!P::from([1, 1, 0, 0]).is_one());
+        assert!(!P::from([0, 1, 0, 0]).is_one());
+        assert!(!P::from([0, 1, 0, 0]).is_zero());
+        // In the zero ring (N = 0), 1 = 0
+        assert!(Poly::<Modular<Q>, 0>::zero().is_one());
+    }
+    #[test]
+    fn test_scale_in_place() {
+        let p = P::from([1, 2, 3, Q - 1]);
+        let c = Modular::from([7]);
+        let mut q = p;
+        q.scale_in_place(c);
+        assert_eq!(q, p.scale(c));
+        assert_eq!(q, P::from([7, 14, 21, Q - 7]));
+        assert_eq!(q, P::from_base(c) * p);
+    }
+    #[test]
+    fn test_affine() {
+        let x = P::from([1, 2, 3, 4]);
+        let a = P::from([0, 1, Q - 1, 7]);
+        let b = P::from([5, 0, 9, Q - 2]);
+        assert_eq!(x.affine(&a, &b), a * x + b);
+        assert_eq!(x.affine(&P::one(), &P::zero()), x);
+        assert_eq!(x.affine(&P::zero(), &b), b);
+    }
+    #[test]
+    fn test_eval_at_matrix() {
+        let m = |x| Modular::<Q>::from([x]);
+        let a = Matrix([[1, 2], [3, 4]].map(|row| row.map(m)));
+        // x^2 - 1
+        let p = P::from([Q - 1, 0, 1, 0]);
+        assert_eq!(p.eval_at_matrix(&a), a * a - Matrix::one());
+        assert_eq!(P::from([5, 0, 0, 0]).eval_at_matrix(&a), Matrix::from(m(5)));
+        assert!(P::zero().eval_at_matrix(&a).is_zero());
+        // The negacyclic matrix of x satisfies X^4 = -1, so evaluation there is the ring
+        // map Poly -> Matrix: it sends q to q's own negacyclic matrix
+        let x = P::from([0, 1, 0, 0]).to_negacyclic_matrix();
+        let q = P::from([3, 1, 4, 1]);
+        assert_eq!(q.eval_at_matrix(&x), q.to_negacyclic_matrix());
+    }
+    #[test]
+    fn test_gram_matrix() {
+        use crate::matrices::Vector;
+        let p = P::from([1, 2, Q - 3, 4]);
+        let b = P::from([5, 0, 7, Q - 1]);
+        assert_eq!(p.to_negacyclic_matrix() * Vector(b.0), Vector((p * b).0));
+        let g = p.gram_matrix();
+        assert_eq!(g.transpose(), g);
+        // Every diagonal entry is ||p||^2 = 1 + 4 + 9 + 16, as multiplying by x^j only
+        // permutes and negates coefficients
+        for j in 0..4 {
+            assert_eq!(g.0[j][j], Modular::from([30]));
+        }
+        // x^k is a signed permutation, so its Gram matrix is the identity
+        assert!(P::from([0, 0, 1, 0]).gram_matrix().is_one());
+    }
+    #[test]
+    fn test_x_to_the_n() {
+        let x = P::from([0, 1, 0, 0]);
+        assert_eq!(x * x, P::from([0, 0, 1, 0]));
+        // x * x^3 wraps around to x^4 = -1
+        assert_eq!(x * x * x * x, -P::one());
+        let p = P::from([1, 2, 3, 4]);
+        // Multiplying by x shifts, negating the coefficient that wraps
+        assert_eq!(x * p, P::from([Q - 4, 1, 2, 3]));
+    }
+    #[test]
+    fn test_mul() {
+        let one_plus_x = P::from([1, 1, 0, 0]);
+        assert_eq!(one_plus_x * one_plus_x, P::from([1, 2, 1, 0]));
+        let p = P::from([1, 2, 3, 4]);
+        assert_eq!(p * P::one(), p);
+        assert_eq!(p * one_plus_x, one_plus_x * p);
+        // (1 + 2x + 3x^2 + 4x^3)(1 + x) = 1 + 3x + 5x^2 + 7x^3 + 4x^4
+        assert_eq!(p * one_plus_x, P::from([Q - 3, 3, 5, 7]));
+    }
+    #[test]
+    fn test_ring_switch() {
+        type Big = Poly<Modular<Q>, 8>;
+        let b = P::from([1, 2, Q - 3, 4]);
+        let c = P::from([0, 5, 1, 0]);
+        let up: Big = b.ring_switch_up();
+        assert_eq!(up, Big::from([1, 0, 2, 0, Q - 3, 0, 4, 0]));
+        // The inclusion is a ring homomorphism, so x^4 = -1 survives as (x^2)^4 = -1
+        let (up_c, up_bc): (Big, Big) = (c.ring_switch_up(), (b * c).ring_switch_up());
+        assert_eq!(up * up_c, up_bc);
+        // Down then up, or up then down, is multiplication by 2 on the subring
+        let two = P::from([2, 0, 0, 0]);
+        assert_eq!(up.ring_switch_down::<4>(), two * b);
+        assert_eq!(up.ring_switch_down::<4>().ring_switch_up::<8>(), up + up);
+        // The trace sees only even coefficients and is R_4-linear
+        let a = Big::from([9, 8, 7, 6, 5, 4, 3, 2]);
+        assert_eq!(a.ring_switch_down::<4>(), P::from([18, 14, 10, 6]));
+        assert_eq!((up * a).ring_switch_down::<4>(), b * a.ring_switch_down());
+        // Z/QZ has 1/2 for odd Q, so the halved trace inverts the inclusion exactly
+        let half = Modular::from([2]).inverse().unwrap();
+        assert_eq!(up.ring_switch_down::<4>().scale(half), b);
+    }
+    #[test]
+    #[should_panic(expected = "ring switching needs N = 2M")]
+    fn test_ring_switch_wrong_size() {
+        P::one().ring_switch_down::<3>();
+    }
+    #[test]
+    fn test_twist() {
+        use crate::rings::RingElement;
+        // Multiplication in (Z/QZ)[x]/(x^4 - 1)
+        fn cyclic_mul(a: &[Modular<Q>; 4], b: &[Modular<Q>; 4]) -> [Modular<Q>; 4] {
+            let mut out = [Modular::zero(); 4];
+            for (i, x) in a.iter().enumerate() {
+                for (j, y) in b.iter().enumerate() {
+                    out[(i + j) % 4] += *x * *y;
+                }
+            }
+            out
+        }
+        let psi = (2..Q)
+            .map(|g| Modular::<Q>::from([g]))
+            .find(|g| RingElement::pow(*g, 4) == -Modular::one())
+            .unwrap();
+        let a = [1, 2, 3, 4].map(|x| Modular::from([x]));
+        let b = [5, 0, Q - 1, 7].map(|x| Modular::from([x]));
+        assert_eq!(P::twist(&a, psi).untwist(psi), a);
+        let product = P::twist(&a, psi) * P::twist(&b, psi);
+        assert_eq!(product.untwist(psi), cyclic_mul(&a, &b));
+        // The cyclic x^4 = 1 becomes the negacyclic (psi x)^4 = -x^4 = 1
+        let x = [0, 1, 0, 0].map(|x| Modular::from([x]));
+        assert!(RingElement::pow(P::twist(&x, psi), 4).is_one());
+        assert_ne!(
+            product,
+            P::from(cyclic_mul(&a, &b).map(|c| c.to_coeffs()[0]))
+        );
+    }
+    #[test]
+    #[should_panic(expected = "twisting needs psi^N = -1")]
+    fn test_twist_bad_root() {
+        P::twist(&[Modular::one(); 4], Modular::from([2]));
+    }
+    #[test]
+    fn test_pow() {
+        use crate::rings::RingElement;
+        let x = P::from([0, 1, 0, 0]);
+        assert_eq!(RingElement::pow(x, 4), -P::one());
+        assert_eq!(RingElement::pow(x, 3), P::from([0, 0, 0, 1]));
+        assert!(RingElement::pow(x, 8).is_one());
+        assert_eq!(RingElement::pow(x, 8 * 1000 + 5), -x);
+        // The reduction x^N = -1 at a size where squaring wraps around many times
+        let mut y = Poly::<Modular<Q>, 256>::zero();
+        y.0[1] = Modular::one();
+        assert_eq!(RingElement::pow(y, 256), -Poly::one());
+        assert_eq!(RingElement::pow(y, 255) * y, -Poly::one());
+    }
+    #[test]
+    fn test_pow_window() {
+        use crate::rings::RingElement;
+        let x = P::from([0, 1, 0, 0]);
+        // x has order 8, as x^4 = -1
+        assert_eq!(x.pow_window(8 * 1000 + 3, 3), x * x * x);
+        let p = P::from([1, 2, 3, 4]);
+        for &exp in &[0, 1, 5, 64, 12288, 1 << 40] {
+            for window in 1..=5 {
+                assert_eq!(p.pow_window(exp, window), RingElement::pow(p, exp));
+            }
+        }
+    }
+    #[test]
+    fn test_pow_wnaf() {
+        use crate::rings::RingElement;
+        // c x^k is a unit, with inverse c^-1 * (-x^(4 - k)) as x^4 = -1
+        let c = Modular::<Q>::from([5]);
+        let c_inv = c.inverse().unwrap();
+        let x = P::from([0, 1, 0, 0]);
+        for k in 0..4 {
+            let u = P::from_base(c) * RingElement::pow(x, k);
+            let u_inv = -P::from_base(c_inv) * RingElement::pow(x, 4 - k);
+            assert!((u * u_inv).is_one());
+            for &exp in &[0, 1, 7, 64, 12288, 1 << 40, u64::MAX] {
+                for window in 2..=5 {
+                    assert_eq!(
+                        u.pow_wnaf_with_inverse(u_inv, exp, window),
+                        RingElement::pow(u, exp)
+                    );
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_ring_element() {
+        use crate::rings::geometric_sum;
+        let x = P::from([0, 1, 0, 0]);
+        assert_eq!(geometric_sum(x, 4), P::from([1, 1, 1, 1]));
+        // (1 + x + x^2 + x^3)(1 + x^4) = 0, as x^4 = -1
+        assert!(geometric_sum(x, 8).is_zero());
+        // The telescoping identity (p - 1)(1 + p + ... + p^(k-1)) = p^k - 1
+        let p = P::from([3, 1, 4, 1]);
+        for k in 0..6 {
+            assert_eq!(
+                (p - P::one()) * geometric_sum(p, k),
+                RingElement::pow(p, k) - P::one()
+            );
+        }
+    }
+    #[test]
+    fn test_norms() {
+        let p = Poly::<Modular<13>, 4>::from([12, 2, 9, 0]);
+        // Centered: (-1, 2, -4, 0)
+        assert_eq!(p.norm_inf(), 4);
+        assert_eq!(p.norm_l2_squared(), 1 + 4 + 16);
+        assert_eq!(P::zero().norm_inf(), 0);
+        // A fresh Gaussian error is far below the tail cut of 12 sigma
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let e: Poly<Modular<Q>, 256> = crate::sampling::sample_gaussian_poly(&mut rng, 3.2);
+        assert!(e.norm_inf() <= 39);
+        assert!(e.norm_l2_squared() < 2 * 256 * 11);
+    }
+    #[test]
+    fn test_centered_coeffs() {
+        // Q/2 = 6 stays positive, while Q/2 + 1 = 7 is -6
+        let p = Poly::<Modular<13>, 4>::from([6, 7, 12, 0]);
+        assert_eq!(p.centered_coeffs(), [6, -6, -1, 0]);
+        assert_eq!(p.centered_coeffs().iter().sum::<i64>(), -1);
+    }
+    #[test]
+    fn test_noise_profile() {
+        // Centered coefficients (2, -2, 2, -2): mean 0, standard deviation 2
+        let p = P::from([2, Q - 2, 2, Q - 2]);
+        assert_eq!(p.noise_profile(), (-2, 2, 2.0));
+        let (min, max, std_dev) = P::from([0, 5, Q - 9, 1]).noise_profile();
+        assert_eq!((min, max), (-9, 5));
+        assert!(std_dev > 0.0);
+        assert_eq!(P::zero().noise_profile(), (0, 0, 0.0));
+        assert_eq!(Poly::<Modular<Q>, 0>::zero().noise_profile(), (0, 0, 0.0));
+    }
+    #[test]
+    fn test_bits() {
+        let bits = vec![true, false, true, true];
+        let p = P::from_bits(&bits).unwrap();
+        assert_eq!(p, P::from([1, 0, 1, 1]));
+        assert_eq!(p.to_bits(), bits);
+        assert_eq!(P::from_bits(&bits[..3]), None);
+    }
+    #[test]
+    fn test_karatsuba() {
+        fn check<const N: usize>(seed: u32) {
+            let a: Poly<Modular<Q>, N> = Poly(array::from_fn(|i| {
+                Modular::from([(i as u32 * 7919 + seed) % Q])
+            }));
+            let b: Poly<Modular<Q>, N> = Poly(array::from_fn(|i| {
+                Modular::from((i * i * 104729) as i64 + i64::from(3 * seed))
+            }));
+            let fused = a.mul_karatsuba(&b);
+            assert_eq!(fused, negacyclic_reduce(&karatsuba(&a.0, &b.0)));
+            assert_eq!(fused, a * b);
+        }
+        check::<4>(1);
+        check::<16>(2);
+        check::<17>(3);
+        check::<33>(4);
+        check::<64>(5);
+        check::<100>(6);
+        check::<256>(7);
+        check::<0>(8);
+        // Against a full product computed by hand: (1 + x)^2 = 1 + 2x + x^2
+        let full = karatsuba(&[Modular::<Q>::one(); 2], &[Modular::one(); 2]);
+        assert_eq!(
+            full,
+            [1, 2, 1]
+                .iter()
+                .map(|&x| Modular::from([x]))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(negacyclic_reduce::<_, 2>(&full), Poly::from([0, 2]));
+    }
+    #[test]
+    fn test_minimal_polynomial() {
+        use crate::rings::RingElement;
+        let coeffs = |c: &[u32]| c.iter().map(|&x| Modular::from([x])).collect::<Vec<_>>();
+        let x = P::from([0, 1, 0, 0]);
+        assert_eq!(x.minimal_polynomial(), coeffs(&[1, 0, 0, 0, 1]));
+        // x^2 lies in the subring of even powers, where (x^2)^2 = -1
+        assert_eq!((x * x).minimal_polynomial(), coeffs(&[1, 0, 1]));
+        assert_eq!(
+            P::from([5, 0, 0, 0]).minimal_polynomial(),
+            coeffs(&[Q - 5, 1])
+        );
+        assert_eq!(P::zero().minimal_polynomial(), coeffs(&[0, 1]));
+        // A generic element, checked by evaluating its minimal polynomial at it
+        let a = P::from([3, 1, 4, 1]);
+        let min = a.minimal_polynomial();
+        let value = min.iter().enumerate().fold(P::zero(), |acc, (i, c)| {
+            acc + RingElement::pow(a, i as u64).scale(*c)
+        });
+        assert!(value.is_zero());
+        assert!(min.last().unwrap().is_one());
+        assert!(min.len() <= 5);
+    }
+    #[test]
+    #[should_panic(expected = "minimal_polynomial needs a prime modulus")]
+    fn test_minimal_polynomial_composite() {
+        Poly::<Modular<12>, 4>::one().minimal_polynomial();
+    }
+    #[test]
+    fn test_content() {
+        // Over Z/12Z, 4 + 8x + 4x^3 generates the same ideal as 4
+        type P12 = Poly<Modular<12>, 4>;
+        let p = P12::from([4, 8, 0, 4]);
+        assert_eq!(p.content(), Modular::from([4]));
+        assert_eq!(p.primitive_part(), P12::from([1, 2, 0, 1]));
+        assert_eq!(p.primitive_part().scale(p.content()), p);
+        assert!(p.primitive_part().content().is_one());
+        assert_eq!(
+            P12::from([8, 0, 0, 0]).primitive_part(),
+            P12::from([2, 0, 0, 0])
+        );
+        assert_eq!(P12::from([6, 9, 3, 0]).content(), Modular::from([3]));
+        assert!(P12::from([6, 9, 4, 0]).content().is_one());
+        assert!(P12::zero().content().is_zero());
+        assert!(P12::zero().primitive_part().is_zero());
+        // Over a prime modulus only 0 has content other than 1
+        let q = P::from([0, 6, 12, 0]);
+        assert!(q.content().is_one());
+        assert_eq!(q.primitive_part(), q);
+    }
+    #[test]
+    fn test_pack_bytes() {
+        // 13 bits per coefficient and 52 per polynomial, so 20 bytes plus padding span
+        // four polynomials with a partial last one
+        let data: Vec<u8> = (0..20).map(|i| (i * 37 + 250) as u8).collect();
+        let polys: Vec<P> = P::pack_bytes(&data).collect();
+        assert_eq!(polys.len(), 4);
+        assert!(polys
+            .iter()
+            .flat_map(|p| p.0.iter())
+            .all(|c| c.rem_small(Q) < 1 << 13));
+        assert_eq!(P::unpack_bytes(&polys), Some(data.clone()));
+        // Trailing zero bytes survive, and an empty stream is one padding polynomial
+        let zeros = [0u8; 13];
+        let polys: Vec<P> = P::pack_bytes(&zeros).collect();
+        assert_eq!(polys.len(), 3);
+        assert_eq!(P::unpack_bytes(&polys).unwrap(), zeros);
+        let polys: Vec<P> = P::pack_bytes(&[]).collect();
+        assert_eq!(polys, vec![P::from([1 << 7, 0, 0, 0])]);
+        assert_eq!(P::unpack_bytes(&polys), Some(vec![]));
+        // With one bit per coefficient 13 bytes fill exactly 26 polynomials, and the
+        // padding byte takes two more
+        let polys: Vec<Poly<Modular<2>, 4>> = Poly::pack_bytes(&data[..13]).collect();
+        assert_eq!(polys.len(), 28);
+        assert_eq!(Poly::unpack_bytes(&polys[..]), Some(data[..13].to_vec()));
+        let polys: Vec<Poly<Modular<3>, 4>> = Poly::pack_bytes(&data[..2]).collect();
+        assert_eq!(polys.len(), 6);
+        assert_eq!(Poly::unpack_bytes(&polys), Some(data[..2].to_vec()));
+        // Missing padding, or a coefficient too large for its 13 bits
+        assert_eq!(P::unpack_bytes(&[P::zero()]), None);
+        assert_eq!(P::unpack_bytes(&[]), None);
+        assert_eq!(P::unpack_bytes(&[P::from([1 << 7, 0, 1 << 13, 0])]), None);
+    }
+    #[test]
+    #[should_panic(expected = "to_bits on a non-binary polynomial")]
+    fn test_bits_non_binary() {
+        P::from([1, 0, 2, 0]).to_bits();
+    }
+    #[test]
+    fn test_coeff_pow() {
+        let p = P::from([2, 3, Q - 1, 0]);
+        assert_eq!(p.coeff_pow(2), Poly(p.0.map(|c| c.pow(2))));
+        assert_eq!(p.coeff_pow(2), P::from([4, 9, 1, 0]));
+        // Unlike the ring power: (2 + 3x - x^2)^2 has nonzero x^3 and x^4 terms
+        assert_ne!(p.coeff_pow(2), p * p);
+        assert_eq!(p.coeff_pow(0), P::from([1, 1, 1, 1]));
+    }
+    #[test]
+    fn test_dual() {
+        let p = P::from([1, 2, 3, Q - 1]);
+        assert_eq!(p.to_dual().from_dual(), p);
+        assert_eq!(p.from_dual().to_dual(), p);
+        // 4 * (1/4) = 1
+        assert_eq!(P::from([4, 0, 0, 0]).to_dual(), P::one());
+        // Scaling commutes with the ring structure up to the factor N
+        let q = P::from([0, 1, 5, 7]);
+        assert_eq!((p * q).to_dual(), p.to_dual() * q);
+    }
+    #[test]
+    fn test_fixed_weight() {
+        let p = P::from([Q - 1, 0, 1, 0]);
+        assert!(p.verify_fixed_weight(2));
+        assert!(!p.verify_fixed_weight(3));
+        assert!(P::zero().verify_fixed_weight(0));
+    }
+    #[test]
+    fn test_expansion_factor() {
+        assert_eq!(P::expansion_factor(), 4.0);
+        // The extremal pair, with every product coefficient checked against the bound
+        let a = P::from([1, 1, 1, 1]);
+        let b = P::from([1, Q - 1, Q - 1, Q - 1]);
+        let max = (a * b).0.iter().map(|c| c.centered().abs()).max().unwrap();
+        assert_eq!(max as f64, P::expansion_factor());
+    }
+    #[test]
+    fn test_le_bytes() {
+        let p = P::from([1, 2, 3, Q - 1]);
+        let bytes = p.to_le_bytes();
+        assert_eq!(bytes, vec![1, 0, 2, 0, 3, 0, 0x00, 0x30]);
+        assert_eq!(P::from_le_bytes(&bytes), Some(p));
+        assert_eq!(P::from_le_bytes(&bytes[1..]), None);
+        let mut bad = bytes;
+        bad[6] = 0x01;
+        assert_eq!(P::from_le_bytes(&bad), None);
+        let q = Poly::<Modular<40961>, 2>::from([40960, 7]);
+        assert_eq!(Poly::from_le_bytes(&q.to_le_bytes()), Some(q));
+    }
+    #[cfg(feature = "subtle")]
+    #[test]
+    fn test_constant_time() {
+        use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+        let (p, q) = (P::from([1, 2, 3, 4]), P::from([0, 2, 3, 4]));
+        assert!(bool::from(p.ct_eq(&p)));
+        assert!(!bool::from(p.ct_eq(&q)));
+        assert_eq!(P::conditional_select(&p, &q, Choice::from(0)), p);
+        assert_eq!(P::conditional_select(&p, &q, Choice::from(1)), q);
+    }
+    #[test]
+    fn test_random() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2);
+        let p = Poly::<Modular<Q>, 256>::random(&mut rng);
+        assert_ne!(p, Poly::random(&mut rng));
+    }
+}
@@ -0,0 +1,152 @@
+//! Helpers for choosing moduli. Most of the rings in this crate are only useful for
+//! RLWE when their modulus Q is a prime with Q = 1 mod 2N, as then Z/QZ contains the
+//! primitive 2N-th roots of unity the negacyclic NTT needs.
+
+/// Deterministic Miller-Rabin. The bases 2, 7, and 61 are known to suffice for all
+/// n < 4,759,123,141, which covers every u32.
+pub const fn is_prime(n: u32) -> bool {
+    if n < 2 {
+        return false;
+    }
+    // Loops rather than iterators and closures, so this can be a const fn
+    let small = [
+        2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61,
+    ];
+    let mut i = 0;
+    while i < small.len() {
+        if n.is_multiple_of(small[i]) {
+            return n == small[i];
+        }
+        i += 1;
+    }
+    let n = n as u64;
+    // n - 1 = d * 2^s with d odd
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    let bases = [2, 7, 61];
+    let mut i = 0;
+    'witness: while i < bases.len() {
+        let mut x = pow_mod(bases[i], d, n);
+        i += 1;
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        let mut round = 1;
+        while round < s {
+            x = x * x % n;
+            if x == n - 1 {
+                continue 'witness;
+            }
+            round += 1;
+        }
+        return false;
+    }
+    true
+}
+
+/// base^exp mod n, for n < 2^32.
+const fn pow_mod(mut base: u64, mut exp: u64, n: u64) -> u64 {
+    let mut acc = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            acc = acc * base % n;
+        }
+        base = base * base % n;
+        exp >>= 1;
+    }
+    acc
+}
+
+/// Whether Z/qZ supports a negacyclic NTT of dimension n, i.e. q = 1 mod 2n.
+pub const fn is_ntt_friendly(q: u32, n: usize) -> bool {
+    let two_n = 2 * n as u64;
+    q as u64 % two_n == 1
+}
+
+/// The primes q = 1 mod 2n with 2^(bits - 1) <= q < 2^bits, largest first.
+fn ntt_primes(n: usize, bits: u32) -> impl Iterator<Item = u32> {
+    let valid = (2..=32).contains(&bits) && n > 0;
+    let two_n = 2 * n as u64;
+    let (lower, upper) = (1u64 << (bits.clamp(1, 32) - 1), 1u64 << bits.clamp(1, 32));
+    // Candidates are 2nk + 1, scanned downwards from the largest one below 2^bits
+    let top = if valid { (upper - 2) / two_n } else { 0 };
+    (1..=top)
+        .rev()
+        .map(move |k| two_n * k + 1)
+        .take_while(move |q| *q >= lower)
+        .map(|q| q as u32)
+        .filter(|q| is_prime(*q))
+}
+
+/// The largest prime q < 2^bits with q = 1 mod 2n, provided it is still a bits-bit
+/// number (q >= 2^(bits - 1)). Returns None if there is no such prime.
+pub fn find_ntt_prime(n: usize, bits: u32) -> Option<u32> {
+    ntt_primes(n, bits).next()
+}
+
+/// Distinct primes q = 1 mod 2n whose product exceeds 2^bits, for a residue number system
+/// basis. Being distinct primes they are pairwise coprime. The primes are below 2^31,
+/// so each fits a Modular<Q> on its fast addition path, and are taken largest first to
+/// keep the basis short. Panics if n is 0 or there are too few such primes.
+pub fn build_rns_basis(bits: u32, n: usize) -> Vec<u32> {
+    assert!(n > 0, "the ring dimension must be positive");
+    let mut basis = Vec::new();
+    let mut log_product = 0.0;
+    let mut candidates = (2..=31).rev().flat_map(|b| ntt_primes(n, b));
+    while log_product <= f64::from(bits) {
+        let q = candidates
+            .next()
+            .unwrap_or_else(|| panic!("too few primes = 1 mod {} for {} bits", 2 * n, bits));
+        log_product += f64::from(q).log2();
+        basis.push(q);
+    }
+    basis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_is_prime() {
+        let small_primes: Vec<u32> = (0..100).filter(|n| is_prime(*n)).collect();
+        assert_eq!(
+            small_primes,
+            vec![
+                2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79,
+                83, 89, 97
+            ]
+        );
+        assert!(is_prime(12289));
+        assert!(is_prime(4294967291));
+        // Strong pseudoprime to bases 2, 3, 5, and 7
+        assert!(!is_prime(3215031751));
+        assert!(!is_prime(u32::MAX));
+    }
+    #[test]
+    fn test_find_ntt_prime() {
+        for &(n, bits) in &[(256, 14), (256, 23), (512, 16), (1024, 32), (4, 5)] {
+            let q = find_ntt_prime(n, bits).unwrap();
+            assert!(is_prime(q));
+            assert!(is_ntt_friendly(q, n));
+            assert!(u64::from(q) >= 1 << (bits - 1) && u64::from(q) < 1 << bits);
+        }
+        assert_eq!(find_ntt_prime(1024, 12), None);
+    }
+    #[test]
+    fn test_build_rns_basis() {
+        for &(bits, n) in &[(0, 256), (30, 256), (100, 1024), (120, 4096)] {
+            let basis = build_rns_basis(bits, n);
+            let mut product: u128 = 1;
+            for (i, &q) in basis.iter().enumerate() {
+                assert!(is_prime(q) && is_ntt_friendly(q, n));
+                assert!(!basis[..i].contains(&q));
+                product *= u128::from(q);
+            }
+            assert!(product > 1 << bits);
+        }
+        assert_eq!(build_rns_basis(100, 1024).len(), 4);
+        // Far more bits than 31-bit primes can reach in one bit range
+        let basis = build_rns_basis(10000, 8);
+        assert!(basis.iter().map(|q| f64::from(*q).log2()).sum::<f64>() > 10000.0);
+    }
+}
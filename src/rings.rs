@@ -18,14 +18,154 @@
 use alga::general::*;
 use num_traits::identities::{One, Zero};
 use std::convert::From;
-use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
-pub trait FinRankCRing<const RANK: usize> {}
+/// A rank RANK commutative ring. Elements convert to and from their RANK coordinates (as
+/// u32 representatives), which is the uniform serialization boundary for every such ring.
+pub trait FinRankCRing<const RANK: usize>: RingCommutative + From<[u32; RANK]> {
+    fn to_coeffs(&self) -> [u32; RANK];
+    fn from_coeffs(coeffs: [u32; RANK]) -> Self {
+        Self::from(coeffs)
+    }
+}
+
+/// The canonical inclusion S -> R of a base ring S into a ring R built over it, e.g. a
+/// scalar of Z/qZ included into (Z/qZ)[x]/(f(x)) as a constant polynomial.
+pub trait FromBase<S> {
+    fn from_base(s: S) -> Self;
+}
+
+/// The common surface of the rings in this crate (zero, one, is_zero, pow, and the
+/// arithmetic operators that alga's Ring carries), so generic code can use a single
+/// bound rather than a long where clause over alga's traits.
+pub trait RingElement: RingCommutative + Zero + One + Copy {
+    /// Square-and-multiply exponentiation, with x^0 = 1 for every x (including 0).
+    fn pow(self, exp: u64) -> Self {
+        let mut acc = Self::one();
+        let mut base = self;
+        let mut exp = exp;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc *= base;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base *= base;
+            }
+        }
+        acc
+    }
+
+    /// x^exp by left-to-right sliding windows of up to `window` bits, which saves
+    /// multiplications over pow for large exponents at the cost of a table of the
+    /// 2^(window - 1) odd powers x, x^3, x^5, ...
+    ///
+    /// This is the unsigned relative of wNAF exponentiation: NAF's negative digits would
+    /// need x^-1, which a general ring element does not have (see pow_wnaf_with_inverse
+    /// for when it does). Panics unless 1 <= window <= 16.
+    fn pow_window(self, exp: u64, window: usize) -> Self {
+        assert!(
+            (1..=16).contains(&window),
+            "window {} is not in 1..=16",
+            window
+        );
+        let square = self * self;
+        let mut odd_powers = vec![self];
+        for i in 1..(1 << (window - 1)) {
+            odd_powers.push(odd_powers[i - 1] * square);
+        }
+        let bit = |i: usize| (exp >> i) & 1 == 1;
+        let mut acc = Self::one();
+        // Bit positions still to process are 0..top
+        let mut top = 64 - exp.leading_zeros() as usize;
+        while top > 0 {
+            let i = top - 1;
+            if !bit(i) {
+                acc *= acc;
+                top = i;
+                continue;
+            }
+            // The longest run i..=j of at most window bits ending in a 1
+            let mut j = i.saturating_sub(window - 1);
+            while !bit(j) {
+                j += 1;
+            }
+            for _ in j..=i {
+                acc *= acc;
+            }
+            let digit = (exp >> j) & ((1 << (i - j + 1)) - 1);
+            acc *= odd_powers[(digit / 2) as usize];
+            top = j;
+        }
+        acc
+    }
+
+    /// x^exp by its width-`window` non-adjacent form, given inverse = x^-1: exp is
+    /// written with signed odd digits |d| < 2^(window - 1), at most one in every `window`
+    /// consecutive positions, so there are fewer multiplications than pow_window needs
+    /// for the same table size. The tables hold x, x^3, ... and their inverses.
+    ///
+    /// The result is only meaningful if x * inverse = 1. Panics unless
+    /// 2 <= window <= 16.
+    fn pow_wnaf_with_inverse(self, inverse: Self, exp: u64, window: usize) -> Self {
+        assert!(
+            (2..=16).contains(&window),
+            "window {} is not in 2..=16",
+            window
+        );
+        // Least significant digit first. The running value can exceed u64 after
+        // subtracting a negative digit, so it is held in a u128.
+        let mut digits = Vec::new();
+        let mut e = u128::from(exp);
+        while e > 0 {
+            let mut digit = 0;
+            if e & 1 == 1 {
+                digit = (e & ((1 << window) - 1)) as i64;
+                if digit >= 1 << (window - 1) {
+                    digit -= 1 << window;
+                }
+                e = (e as i128 - i128::from(digit)) as u128;
+            }
+            digits.push(digit);
+            e >>= 1;
+        }
+        let odd_powers = |x: Self| {
+            let square = x * x;
+            let mut table = vec![x];
+            for i in 1..(1 << (window - 2)) {
+                table.push(table[i - 1] * square);
+            }
+            table
+        };
+        let (positive, negative) = (odd_powers(self), odd_powers(inverse));
+        let mut acc = Self::one();
+        for &digit in digits.iter().rev() {
+            acc *= acc;
+            if digit > 0 {
+                acc *= positive[(digit / 2) as usize];
+            } else if digit < 0 {
+                acc *= negative[(-digit / 2) as usize];
+            }
+        }
+        acc
+    }
+}
 
-impl<T: RingCommutative + From<[u32; RANK]>, const RANK: usize> FinRankCRing<RANK> for T {}
+impl<T: RingCommutative + Zero + One + Copy> RingElement for T {}
+
+/// 1 + x + ... + x^(k-1), using nothing but the RingElement bound, so tests can check the
+/// trait over each ring type.
+#[cfg(test)]
+pub(crate) fn geometric_sum<R: RingElement>(x: R, k: u64) -> R {
+    (0..k).fold(R::zero(), |acc, i| acc + x.pow(i))
+}
 
 pub mod modular {
     use super::*;
+    use rand::distributions::{Distribution, Standard};
+    use rand::Rng;
+    use std::convert::TryFrom;
 
     /// The ring Z/qZ for arbitrary [1^] q.
     /// Elements are represented as integers in [0, ..., Q)
@@ -37,28 +177,471 @@ pub mod modular {
     /// [1]: If Q is too large one has to convert from u32's to u64's for addition/multiplication.
     /// "Too large" is determined at compile time, so this should not have a runtime impact if
     /// Q < 2^31 (for addition) or Q < 2^16 (for multiplication).
+    ///
+    /// The Ord impl compares representatives in [0, Q), e.g. for sorting or BTreeMap keys.
+    /// It is not compatible with the ring operations (5 < 6 but 5 + 8 > 6 + 8 mod 13).
 
-    #[derive(Clone, Copy, PartialEq, Debug, Default, Alga)]
+    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default, Alga)]
     #[alga_traits(RingCommutative(Additive, Multiplicative))]
     pub struct Modular<const Q: u32>(u32);
 
+    impl<const Q: u32> fmt::Display for Modular<Q> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{} (mod {})", self.0, Q)
+        }
+    }
+
+    // Constant-time comparison and selection on the representative, for branch-free
+    // handling of secrets. The derived PartialEq is not constant time.
+    #[cfg(feature = "subtle")]
+    impl<const Q: u32> subtle::ConstantTimeEq for Modular<Q> {
+        fn ct_eq(&self, other: &Self) -> subtle::Choice {
+            self.0.ct_eq(&other.0)
+        }
+    }
+
+    #[cfg(feature = "subtle")]
+    impl<const Q: u32> subtle::ConditionallySelectable for Modular<Q> {
+        fn conditional_select(a: &Self, b: &Self, choice: subtle::Choice) -> Self {
+            Modular(u32::conditional_select(&a.0, &b.0, choice))
+        }
+    }
+
+    /// An encoded value outside [0, modulus), where a canonical representative was required.
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub struct NonCanonical {
+        pub value: u32,
+        pub modulus: u32,
+    }
+
+    impl fmt::Display for NonCanonical {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                f,
+                "{} is not a canonical representative mod {}",
+                self.value, self.modulus
+            )
+        }
+    }
+
+    impl std::error::Error for NonCanonical {}
+
     impl<const Q: u32> From<[u32; 1]> for Modular<Q> {
         fn from(x: [u32; 1]) -> Self {
             Modular(x[0] % Q)
         }
     }
+    /// Reduces a signed integer, so -1 maps to Q - 1.
+    impl<const Q: u32> From<i64> for Modular<Q> {
+        fn from(x: i64) -> Self {
+            Modular(x.rem_euclid(Q.into()) as u32)
+        }
+    }
+    impl<const Q: u32> FinRankCRing<1> for Modular<Q> {
+        fn to_coeffs(&self) -> [u32; 1] {
+            [self.0]
+        }
+    }
+    impl<const Q: u32> FromBase<Modular<Q>> for Modular<Q> {
+        fn from_base(s: Modular<Q>) -> Self {
+            s
+        }
+    }
+
+    // const fn counterparts of From<[u32; 1]>, to_coeffs, Mul and RingElement::pow, for
+    // tables built at compile time (trait methods cannot be const on stable Rust).
+    impl<const Q: u32> Modular<Q> {
+        /// x mod Q.
+        pub const fn new_const(x: u32) -> Self {
+            Modular(x % Q)
+        }
+
+        /// The representative in [0, Q).
+        pub const fn val(self) -> u32 {
+            self.0
+        }
+
+        pub const fn mul_const(self, other: Self) -> Self {
+            Modular((self.0 as u64 * other.0 as u64 % Q as u64) as u32)
+        }
+
+        /// Square-and-multiply, with x^0 = 1.
+        pub const fn pow_const(self, mut exp: u64) -> Self {
+            let mut acc = Self::new_const(1);
+            let mut base = self;
+            while exp > 0 {
+                if exp & 1 == 1 {
+                    acc = acc.mul_const(base);
+                }
+                base = base.mul_const(base);
+                exp >>= 1;
+            }
+            acc
+        }
+    }
+
+    impl<const Q: u32> Modular<Q> {
+        /// Encodes a symbol m of Z/PZ as the multiple of Q/P nearest to m * Q/P.
+        /// This generalizes encoding a single bit as 0 or round(Q/2) to P-ary plaintexts.
+        /// Panics if P = 0.
+        pub fn encode_symbol<const P: u32>(m: u32) -> Self {
+            assert!(P > 0, "the plaintext modulus P must be positive");
+            // floor(m * Q/P + 1/2), computed exactly as floor((2mQ + P) / 2P). 2mQ can
+            // reach 2^65, so this is done in u128.
+            let m: u128 = (m % P).into();
+            let (p, q): (u128, u128) = (P.into(), Q.into());
+            Modular::from([((2 * m * q + p) / (2 * p)) as u32])
+        }
+
+        /// Decodes to the symbol m of Z/PZ whose encoding is nearest, i.e. round(x * P/Q) mod P.
+        /// Ties (only possible when x * P/Q is exactly a half-integer) round up. Panics if
+        /// P = 0.
+        pub fn decode_symbol<const P: u32>(self) -> u32 {
+            assert!(P > 0, "the plaintext modulus P must be positive");
+            // As in encode_symbol, 2xP can exceed 64 bits
+            let x: u128 = self.0.into();
+            let (p, q): (u128, u128) = (P.into(), Q.into());
+            (((2 * x * p + q) / (2 * q)) % p) as u32
+        }
+
+        /// Reduces a signed integer into [0, Q), for intermediates too large for 64 bits.
+        pub fn from_i128(x: i128) -> Self {
+            Modular(x.rem_euclid(Q.into()) as u32)
+        }
+
+        /// Splits the representative of x in (-Q/2, Q/2] into (is_negative, absolute value).
+        pub fn sign_magnitude(self) -> (bool, u32) {
+            if self.0 > Q / 2 {
+                (true, Q - self.0)
+            } else {
+                (false, self.0)
+            }
+        }
+
+        /// The representative of x in (-Q/2, Q/2], the inverse of From<i64> on that range.
+        pub fn centered(&self) -> i64 {
+            if self.0 <= Q / 2 {
+                self.0 as i64
+            } else {
+                self.0 as i64 - Q as i64
+            }
+        }
+
+        /// The integer congruent to x closest to c, i.e. the one in
+        /// [c - floor((Q - 1) / 2), c + floor(Q / 2)]. For even Q a tie between two
+        /// representatives Q/2 away from c goes to the larger one. Panics if the result
+        /// does not fit in an i64.
+        pub fn representative_near(self, c: i64) -> i64 {
+            let q = i128::from(Q);
+            let lo = i128::from(c) - (q - 1) / 2;
+            let r = lo + (i128::from(self.0) - lo).rem_euclid(q);
+            i64::try_from(r).expect("representative out of range for i64")
+        }
+
+        // Integer operations on the canonical representative in [0, Q). These are not ring
+        // operations: e.g. parity is not preserved by addition when Q is odd.
+
+        /// Whether the representative in [0, Q) is odd.
+        pub fn parity(self) -> bool {
+            self.0 % 2 == 1
+        }
+
+        /// The representative in [0, Q), reduced mod m.
+        pub fn rem_small(self, m: u32) -> u32 {
+            self.0 % m
+        }
+
+        /// The multiplicative inverse of x, via the extended Euclidean algorithm.
+        /// Works for any Q, returning None when gcd(x, Q) != 1 (in particular for 0).
+        pub fn inverse(&self) -> Option<Self> {
+            // Invariant: r_i = t_i * x mod Q
+            let (mut r0, mut r1): (i64, i64) = (Q.into(), self.0.into());
+            let (mut t0, mut t1): (i64, i64) = (0, 1);
+            while r1 != 0 {
+                let quotient = r0 / r1;
+                let r2 = r0 - quotient * r1;
+                let t2 = t0 - quotient * t1;
+                r0 = r1;
+                r1 = r2;
+                t0 = t1;
+                t1 = t2;
+            }
+            if r0 != 1 {
+                return None;
+            }
+            Some(Modular(t0.rem_euclid(Q.into()) as u32))
+        }
+
+        /// The inverses of all of elems, or None if any is not invertible. Montgomery's
+        /// trick: invert the product of all the elements once, then peel the individual
+        /// inverses off it using the prefix products, for 3(n - 1) multiplications in all.
+        pub fn batch_inverse(elems: &[Self]) -> Option<Vec<Self>> {
+            // prefix[i] = elems[0] * ... * elems[i - 1]
+            let mut prefix = Vec::with_capacity(elems.len());
+            let mut acc = Self::one();
+            for x in elems {
+                prefix.push(acc);
+                acc *= *x;
+            }
+            // The product is a unit iff every factor is
+            let mut acc_inv = acc.inverse()?;
+            let mut out = vec![Self::zero(); elems.len()];
+            for i in (0..elems.len()).rev() {
+                out[i] = acc_inv * prefix[i];
+                acc_inv *= elems[i];
+            }
+            Some(out)
+        }
+
+        /// num / den mod Q, or None if den is not invertible mod Q.
+        pub fn from_rational(num: i64, den: i64) -> Option<Self> {
+            let den = Self::from_i128(den.into()).inverse()?;
+            Some(Self::from_i128(num.into()) * den)
+        }
+
+        /// x^exp, as in RingElement::pow, but callable without that trait in scope. The
+        /// multiplications go through Mul, so are overflow-safe for every Q.
+        pub fn pow(&self, exp: u64) -> Self {
+            RingElement::pow(*self, exp)
+        }
+
+        /// x^exp by wNAF exponentiation (see RingElement::pow_wnaf_with_inverse), using
+        /// inverse for the negative digits. Non-units have no inverse, so for them this is
+        /// pow_window. Panics unless 2 <= window <= 16.
+        pub fn pow_wnaf(self, exp: u64, window: usize) -> Self {
+            match self.inverse() {
+                Some(inverse) => self.pow_wnaf_with_inverse(inverse, exp, window),
+                None => {
+                    assert!(
+                        (2..=16).contains(&window),
+                        "window {} is not in 2..=16",
+                        window
+                    );
+                    self.pow_window(exp, window)
+                }
+            }
+        }
+
+        /// self + a * b with a single reduction. The unreduced sum is below Q^2 < 2^64, so
+        /// this is valid for every Q.
+        pub fn mul_add(self, a: Self, b: Self) -> Self {
+            barrett_reduce(u64::from(self.0) + u64::from(a.0) * u64::from(b.0))
+        }
+
+        /// x^exp for prime Q, first reducing exp mod Q - 1 (the order of the unit group,
+        /// by Fermat) so huge exponents cost no more than small ones.
+        pub fn pow_reduced(self, exp: u64) -> Self {
+            debug_assert!(
+                crate::primes::is_prime(Q),
+                "pow_reduced needs a prime modulus"
+            );
+            if self.is_zero() {
+                // Not a unit, so the exponent can't be reduced (0^(Q-1) is 0, not 1)
+                return RingElement::pow(self, exp);
+            }
+            RingElement::pow(self, exp % u64::from(Q - 1))
+        }
+
+        /// The Legendre symbol (x / Q) for prime Q: 0 if x = 0, 1 if x is a nonzero square,
+        /// and -1 otherwise. Computed by Euler's criterion, x^((Q - 1)/2).
+        pub fn legendre(&self) -> i32 {
+            debug_assert!(crate::primes::is_prime(Q), "legendre needs a prime modulus");
+            if self.is_zero() {
+                0
+            } else if Q == 2 || self.pow(u64::from(Q - 1) / 2).is_one() {
+                1
+            } else {
+                -1
+            }
+        }
+
+        /// A square root of x for prime Q, or None if x is not a square. For Q = 3 mod 4
+        /// this is x^((Q + 1)/4), and otherwise it is found by Tonelli-Shanks.
+        pub fn sqrt(&self) -> Option<Self> {
+            match self.legendre() {
+                0 => return Some(Self::zero()),
+                -1 => return None,
+                _ => {}
+            }
+            if Q == 2 {
+                return Some(*self);
+            }
+            if Q % 4 == 3 {
+                return Some(self.pow(u64::from(Q + 1) / 4));
+            }
+            // Q - 1 = d * 2^s with d odd
+            let s = (Q - 1).trailing_zeros();
+            let d = u64::from((Q - 1) >> s);
+            let non_residue = (2..Q)
+                .map(|z| Modular::<Q>::from([z]))
+                .find(|z| z.legendre() == -1)
+                .unwrap();
+            // Invariant: r^2 = x * t, with t of order dividing 2^m and c of order 2^m
+            let mut m = s;
+            let mut c = non_residue.pow(d);
+            let mut t = self.pow(d);
+            let mut r = self.pow(d.div_ceil(2));
+            while !t.is_one() {
+                // The least i with t^(2^i) = 1, which is below m
+                let mut i = 0;
+                let mut t_pow = t;
+                while !t_pow.is_one() {
+                    t_pow *= t_pow;
+                    i += 1;
+                }
+                let b = c.pow(1 << (m - i - 1));
+                m = i;
+                c = b * b;
+                t *= c;
+                r *= b;
+            }
+            Some(r)
+        }
+
+        /// Reads an untrusted representative, reducing it mod Q if it is out of range.
+        pub fn deserialize_lenient(x: u32) -> Self {
+            Modular::from([x])
+        }
+
+        /// Reads an untrusted representative, rejecting anything outside [0, Q) rather
+        /// than reducing it, e.g. so a tampered ciphertext is not silently accepted.
+        pub fn deserialize_strict(x: u32) -> Result<Self, NonCanonical> {
+            if x < Q {
+                Ok(Modular(x))
+            } else {
+                Err(NonCanonical {
+                    value: x,
+                    modulus: Q,
+                })
+            }
+        }
+    }
+
+    // Dense little-endian encoding, using just enough bytes for every value in [0, Q)
+    impl<const Q: u32> Modular<Q> {
+        /// The width of the byte encoding: 1 for Q <= 2^8, 2 for Q <= 2^16, and so on.
+        pub const BYTES: usize = (32 - (Q - 1).leading_zeros()).div_ceil(8) as usize;
+
+        /// The representative in [0, Q) as BYTES little-endian bytes.
+        pub fn to_le_bytes(&self) -> Vec<u8> {
+            self.0.to_le_bytes()[..Self::BYTES].to_vec()
+        }
+
+        /// Inverse of to_le_bytes, or None if bytes is not BYTES long or encodes a value
+        /// outside [0, Q).
+        pub fn from_le_bytes(bytes: &[u8]) -> Option<Self> {
+            if bytes.len() != Self::BYTES {
+                return None;
+            }
+            let mut word = [0u8; 4];
+            word[..Self::BYTES].copy_from_slice(bytes);
+            Self::deserialize_strict(u32::from_le_bytes(word)).ok()
+        }
+    }
+
+    impl<const Q: u32> Modular<Q> {
+        /// floor((2^64 - 1) / Q), the precomputed constant for Barrett reduction
+        const BARRETT_M: u64 = u64::MAX / Q as u64;
+    }
+
+    /// Reduces x mod Q without a division, by estimating floor(x / Q) as
+    /// floor(x * floor((2^64 - 1) / Q) / 2^64). The estimate is off by at most one, so a
+    /// single conditional subtraction suffices and every x: u64 is a valid input (in
+    /// particular any product of two reduced values, x < Q^2).
+    pub fn barrett_reduce<const Q: u32>(x: u64) -> Modular<Q> {
+        let quotient = ((u128::from(x) * u128::from(Modular::<Q>::BARRETT_M)) >> 64) as u64;
+        let modulus: u64 = Q.into();
+        let r = x - quotient * modulus;
+        if r >= modulus {
+            Modular((r - modulus) as u32)
+        } else {
+            Modular(r as u32)
+        }
+    }
+
+    /// The table of (a + b) mod q (or (a * b) mod q) for a, b < q. Entries outside that
+    /// q x q square are unused and left 0.
+    const fn small_table(q: u32, mul: bool) -> [[u8; 256]; 256] {
+        let mut table = [[0u8; 256]; 256];
+        let mut a = 0;
+        while a < q {
+            let mut b = 0;
+            while b < q {
+                let c = if mul { a * b } else { a + b };
+                table[a as usize][b as usize] = (c % q) as u8;
+                b += 1;
+            }
+            a += 1;
+        }
+        table
+    }
+
+    // Table-based arithmetic for tiny moduli, where a lookup can beat a multiply and a
+    // reduction in inner loops. The lookups take a fixed number of instructions but are
+    // not cache-timing safe, as the address depends on the operands.
+    impl<const Q: u32> Modular<Q> {
+        /// Evaluating this fails compilation for moduli too large to tabulate.
+        const SMALL_MODULUS: () = assert!(Q < 256, "table arithmetic needs Q < 256");
+        const ADD_TABLE: [[u8; 256]; 256] = small_table(Q, false);
+        const MUL_TABLE: [[u8; 256]; 256] = small_table(Q, true);
+
+        /// x + y by table lookup. Only compiles for Q < 256.
+        pub fn add_table(self, other: Self) -> Self {
+            let () = Self::SMALL_MODULUS;
+            // Borrowing the const promotes it to a static, rather than a stack copy
+            let table: &'static [[u8; 256]; 256] = &Self::ADD_TABLE;
+            Modular(table[self.0 as usize][other.0 as usize].into())
+        }
+
+        /// x * y by table lookup. Only compiles for Q < 256.
+        pub fn mul_table(self, other: Self) -> Self {
+            let () = Self::SMALL_MODULUS;
+            let table: &'static [[u8; 256]; 256] = &Self::MUL_TABLE;
+            Modular(table[self.0 as usize][other.0 as usize].into())
+        }
+    }
+
+    /// Uniformly random elements of Z/QZ. This rejection samples u32's, discarding draws in
+    /// the biased tail [Q * floor(2^32 / Q), 2^32) so that reducing mod Q favors no residue.
+    ///
+    /// For Q a power of two there is no tail: the low log2(Q) bits of one u32 are already
+    /// uniform, so that path (chosen at compile time, as Q is a constant) just masks, with
+    /// no division and no loop, so in constant time.
+    impl<const Q: u32> Distribution<Modular<Q>> for Standard {
+        fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Modular<Q> {
+            if Q.is_power_of_two() {
+                return Modular(rng.gen::<u32>() & (Q - 1));
+            }
+            let modulus: u64 = Q.into();
+            let limit = (1 << 32) / modulus * modulus;
+            loop {
+                let x: u32 = rng.gen();
+                if u64::from(x) < limit {
+                    return Modular(x % Q);
+                }
+            }
+        }
+    }
+
+    impl<const Q: u32> Modular<Q> {
+        /// A uniformly random element, see the Distribution impl.
+        pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+            rng.gen()
+        }
+    }
+
     macro_rules! checked_opp {
         ($func:ident, $bound:ident, $checked_func:ident) => {
             impl<const Q: u32> $bound<Modular<Q>> for Modular<Q> {
                 type Output = Modular<Q>;
+                #[allow(clippy::suspicious_arithmetic_impl)]
                 fn $func(self, other: Self) -> Self::Output {
                     if let None = u32::$checked_func(Q, Q) {
                         // Less efficient case if func can overflow
                         // As q is const this is compiled away if not needed
                         let x: u64 = self.0.into();
                         let y: u64 = other.0.into();
-                        let modulus: u64 = Q.into();
-                        Modular::from([(u64::$func(x, y) % modulus) as u32])
+                        barrett_reduce::<Q>(u64::$func(x, y))
                     } else {
                         Modular::from([u32::$func(self.0, other.0)])
                     }
@@ -78,23 +661,24 @@ pub mod modular {
 
     impl<const Q: u32> Sub<Modular<Q>> for Modular<Q> {
         type Output = Modular<Q>;
+        #[allow(clippy::suspicious_arithmetic_impl)]
         fn sub(self, other: Self) -> Self::Output {
             self + other.neg()
         }
     }
 
     macro_rules! op_assign {
-        ($func:ident, $bound:ident, $method:ident) => {
-            impl<const Q: u32> $bound<Modular<Q>> for Modular<Q> {
+        ($t:ident, $func:ident, $bound:ident, $method:ident) => {
+            impl<const Q: u32> $bound<$t<Q>> for $t<Q> {
                 fn $func(&mut self, other: Self) {
                     *self = self.$method(other)
                 }
             }
         };
     }
-    op_assign!(add_assign, AddAssign, add);
-    op_assign!(mul_assign, MulAssign, mul);
-    op_assign!(sub_assign, SubAssign, sub);
+    op_assign!(Modular, add_assign, AddAssign, add);
+    op_assign!(Modular, mul_assign, MulAssign, mul);
+    op_assign!(Modular, sub_assign, SubAssign, sub);
 
     impl<const Q: u32> Zero for Modular<Q> {
         fn zero() -> Self {
@@ -110,35 +694,177 @@ pub mod modular {
             Modular(1)
         }
     }
-    // TODO: Write macros to generate the below
-    // from Add, AddAssign, Mul, MulAssign, Sub, One, and Zero
-    impl<const Q: u32> Identity<Additive> for Modular<Q> {
-        fn identity() -> Self {
-            Self::zero()
+    // alga's structure traits, generated from Add, Mul, Sub, One, and Zero
+    macro_rules! alga_impls {
+        ($t:ident) => {
+            impl<const Q: u32> Identity<Additive> for $t<Q> {
+                fn identity() -> Self {
+                    Self::zero()
+                }
+            }
+
+            impl<const Q: u32> Identity<Multiplicative> for $t<Q> {
+                fn identity() -> Self {
+                    Self::one()
+                }
+            }
+
+            impl<const Q: u32> AbstractMagma<Additive> for $t<Q> {
+                fn operate(&self, other: &Self) -> Self {
+                    *self + *other
+                }
+            }
+
+            impl<const Q: u32> TwoSidedInverse<Additive> for $t<Q> {
+                fn two_sided_inverse(&self) -> Self {
+                    Self::zero() - *self
+                }
+            }
+
+            impl<const Q: u32> AbstractMagma<Multiplicative> for $t<Q> {
+                fn operate(&self, other: &Self) -> Self {
+                    *self * *other
+                }
+            }
+        };
+    }
+    alga_impls!(Modular);
+
+    /// Division by an invertible element. Panics if the divisor shares a factor with Q.
+    impl<const Q: u32> Div<Modular<Q>> for Modular<Q> {
+        type Output = Modular<Q>;
+        #[allow(clippy::suspicious_arithmetic_impl)]
+        fn div(self, other: Self) -> Self::Output {
+            match other.inverse() {
+                Some(inv) => self * inv,
+                None => panic!("division by {}, which is not invertible mod {}", other.0, Q),
+            }
+        }
+    }
+    op_assign!(Modular, div_assign, DivAssign, div);
+
+    // By-reference versions of the operators, so generic code written against &R works for
+    // Modular too
+    macro_rules! ref_opp {
+        ($func:ident, $bound:ident) => {
+            impl<'a, const Q: u32> $bound<&'a Modular<Q>> for &'a Modular<Q> {
+                type Output = Modular<Q>;
+                fn $func(self, other: Self) -> Self::Output {
+                    (*self).$func(*other)
+                }
+            }
+
+            impl<'a, const Q: u32> $bound<&'a Modular<Q>> for Modular<Q> {
+                type Output = Modular<Q>;
+                fn $func(self, other: &'a Modular<Q>) -> Self::Output {
+                    self.$func(*other)
+                }
+            }
+
+            impl<'a, const Q: u32> $bound<Modular<Q>> for &'a Modular<Q> {
+                type Output = Modular<Q>;
+                fn $func(self, other: Modular<Q>) -> Self::Output {
+                    (*self).$func(other)
+                }
+            }
+        };
+    }
+    ref_opp!(add, Add);
+    ref_opp!(sub, Sub);
+    ref_opp!(mul, Mul);
+    ref_opp!(div, Div);
+
+    impl<const Q: u32> Neg for &Modular<Q> {
+        type Output = Modular<Q>;
+        fn neg(self) -> Self::Output {
+            (*self).neg()
+        }
+    }
+
+    /// The field Z/qZ = GF(q) for prime q.
+    ///
+    /// This is a Modular<Q> which additionally implements alga's Field (in particular
+    /// TwoSidedInverse<Multiplicative>), which would be false for composite Q. Converting
+    /// from Modular<Q> checks that Q is prime in debug builds.
+    #[derive(Clone, Copy, PartialEq, Debug, Default, Alga)]
+    #[alga_traits(Field(Additive, Multiplicative))]
+    pub struct PrimeModular<const Q: u32>(Modular<Q>);
+
+    impl<const Q: u32> From<Modular<Q>> for PrimeModular<Q> {
+        fn from(x: Modular<Q>) -> Self {
+            debug_assert!(
+                crate::primes::is_prime(Q),
+                "GF({}) needs a prime modulus",
+                Q
+            );
+            PrimeModular(x)
         }
     }
 
-    impl<const Q: u32> Identity<Multiplicative> for Modular<Q> {
-        fn identity() -> Self {
-            Self::one()
+    impl<const Q: u32> From<PrimeModular<Q>> for Modular<Q> {
+        fn from(x: PrimeModular<Q>) -> Self {
+            x.0
         }
     }
 
-    impl<const Q: u32> AbstractMagma<Additive> for Modular<Q> {
-        fn operate(&self, other: &Self) -> Self {
-            *self + *other
+    impl<const Q: u32> From<[u32; 1]> for PrimeModular<Q> {
+        fn from(x: [u32; 1]) -> Self {
+            PrimeModular::from(Modular::from(x))
         }
     }
 
-    impl<const Q: u32> TwoSidedInverse<Additive> for Modular<Q> {
-        fn two_sided_inverse(&self) -> Self {
-            Self::zero() - *self
+    impl<const Q: u32> FinRankCRing<1> for PrimeModular<Q> {
+        fn to_coeffs(&self) -> [u32; 1] {
+            self.0.to_coeffs()
+        }
+    }
+
+    macro_rules! prime_forward {
+        ($func:ident, $bound:ident) => {
+            impl<const Q: u32> $bound<PrimeModular<Q>> for PrimeModular<Q> {
+                type Output = PrimeModular<Q>;
+                fn $func(self, other: Self) -> Self::Output {
+                    PrimeModular(self.0.$func(other.0))
+                }
+            }
+        };
+    }
+    prime_forward!(add, Add);
+    prime_forward!(sub, Sub);
+    prime_forward!(mul, Mul);
+    prime_forward!(div, Div);
+    op_assign!(PrimeModular, add_assign, AddAssign, add);
+    op_assign!(PrimeModular, mul_assign, MulAssign, mul);
+    op_assign!(PrimeModular, sub_assign, SubAssign, sub);
+    op_assign!(PrimeModular, div_assign, DivAssign, div);
+
+    impl<const Q: u32> Neg for PrimeModular<Q> {
+        type Output = PrimeModular<Q>;
+        fn neg(self) -> Self::Output {
+            PrimeModular(self.0.neg())
+        }
+    }
+
+    impl<const Q: u32> Zero for PrimeModular<Q> {
+        fn zero() -> Self {
+            PrimeModular(Modular::zero())
+        }
+        fn is_zero(&self) -> bool {
+            self.0.is_zero()
         }
     }
 
-    impl<const Q: u32> AbstractMagma<Multiplicative> for Modular<Q> {
-        fn operate(&self, other: &Self) -> Self {
-            *self * *other
+    impl<const Q: u32> One for PrimeModular<Q> {
+        fn one() -> Self {
+            PrimeModular(Modular::one())
+        }
+    }
+
+    alga_impls!(PrimeModular);
+
+    impl<const Q: u32> TwoSidedInverse<Multiplicative> for PrimeModular<Q> {
+        fn two_sided_inverse(&self) -> Self {
+            Self::one() / *self
         }
     }
 
@@ -157,6 +883,49 @@ pub mod modular {
             assert_eq!(y, x_plus_y);
         }
         #[test]
+        fn test_ref_ops() {
+            // Written only against references, as generic matrix code might be
+            fn mul_add<'a, R>(a: &'a R, b: &'a R, c: &'a R) -> R
+            where
+                &'a R: Mul<&'a R, Output = R>,
+                R: Add<&'a R, Output = R>,
+            {
+                a * b + c
+            }
+            const Q: u32 = 13;
+            let (x, y) = (Modular::<Q>::from([5]), Modular::<Q>::from([9]));
+            let (x_ref, y_ref) = (&x, &y);
+            assert_eq!(x_ref + y_ref, x + y);
+            assert_eq!(x_ref - y_ref, x - y);
+            assert_eq!(x * y_ref, x * y);
+            assert_eq!(x_ref / y, x / y);
+            assert_eq!(-x_ref, -x);
+            assert_eq!(mul_add(x_ref, y_ref, x_ref), x * y + x);
+        }
+        #[test]
+        fn test_display_and_ord() {
+            const Q: u32 = 13;
+            assert_eq!(Modular::<Q>::from([5]).to_string(), "5 (mod 13)");
+            let mut xs = [9, 0, 12, 5, 5].map(|x| Modular::<Q>::from([x]));
+            xs.sort();
+            assert_eq!(xs, [0, 5, 5, 9, 12].map(|x| Modular::<Q>::from([x])));
+            assert!(-Modular::<Q>::one() > Modular::one());
+        }
+        #[cfg(feature = "subtle")]
+        #[test]
+        fn test_constant_time() {
+            use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+            const Q: u32 = 13;
+            for a in 0..Q {
+                for b in 0..Q {
+                    let (x, y) = (Modular::<Q>::from([a]), Modular::<Q>::from([b]));
+                    assert_eq!(bool::from(x.ct_eq(&y)), x == y);
+                    assert_eq!(Modular::conditional_select(&x, &y, Choice::from(0)), x);
+                    assert_eq!(Modular::conditional_select(&x, &y, Choice::from(1)), y);
+                }
+            }
+        }
+        #[test]
         fn test_add_zero() {
             const Q: u32 = 27;
             let x = Modular::<Q>::from([5]);
@@ -182,5 +951,733 @@ pub mod modular {
             let z = Modular::<Q>::from([28]);
             assert_eq!(x * y, z);
         }
+        #[test]
+        fn test_ring_element() {
+            const Q: u32 = 37;
+            let x = Modular::<Q>::from([5]);
+            // (x^4 - 1) / (x - 1) = 624 / 4 = 156 = 8 mod 37
+            assert_eq!(geometric_sum(x, 4), Modular::from([8]));
+            assert_eq!(RingElement::pow(Modular::<Q>::zero(), 0), Modular::one());
+            assert!(geometric_sum(x, 0).is_zero());
+        }
+        #[test]
+        fn test_fin_rank_coeffs() {
+            const Q: u32 = 13;
+            let x = Modular::<Q>::from([9]);
+            assert_eq!(x.to_coeffs(), [9]);
+            assert_eq!(Modular::<Q>::from_coeffs(x.to_coeffs()), x);
+            assert_eq!(Modular::<Q>::from_coeffs([Q + 9]), x);
+            let y = PrimeModular::<Q>::from([9]);
+            assert_eq!(PrimeModular::<Q>::from_coeffs(y.to_coeffs()), y);
+        }
+        #[test]
+        fn test_random_uniform() {
+            use rand::rngs::StdRng;
+            use rand::SeedableRng;
+            const Q: u32 = 7;
+            const DRAWS: usize = 70000;
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut buckets = [0usize; Q as usize];
+            for _ in 0..DRAWS {
+                let x = Modular::<Q>::random(&mut rng);
+                buckets[x.rem_small(Q) as usize] += 1;
+            }
+            // Each bucket expects 10000 draws, with a standard deviation of about 93
+            for count in buckets.iter() {
+                assert!((9600..10400).contains(count), "bucket counts {:?}", buckets);
+            }
+        }
+        #[test]
+        fn test_random_rejects_biased_tail() {
+            // Replays fixed u32 draws
+            struct Replay(Vec<u32>);
+            impl rand::RngCore for Replay {
+                fn next_u32(&mut self) -> u32 {
+                    self.0.remove(0)
+                }
+                fn next_u64(&mut self) -> u64 {
+                    self.next_u32().into()
+                }
+                fn fill_bytes(&mut self, dest: &mut [u8]) {
+                    for byte in dest.iter_mut() {
+                        *byte = self.next_u32() as u8;
+                    }
+                }
+                fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+                    self.fill_bytes(dest);
+                    Ok(())
+                }
+            }
+            // 2^32 = 4 mod 7, so the top 4 values of u32 are the biased tail
+            let mut rng = Replay(vec![u32::MAX, u32::MAX - 3, u32::MAX - 4, 10]);
+            assert_eq!(
+                Modular::<7>::random(&mut rng),
+                Modular::from([u32::MAX - 4])
+            );
+            assert_eq!(Modular::<7>::random(&mut rng), Modular::from([3]));
+        }
+        #[test]
+        fn test_random_power_of_two() {
+            // One u32 per sample, never rejected, even at the very top of the range
+            struct Counting(rand::rngs::StdRng, usize);
+            impl rand::RngCore for Counting {
+                fn next_u32(&mut self) -> u32 {
+                    self.1 += 1;
+                    rand::RngCore::next_u32(&mut self.0)
+                }
+                fn next_u64(&mut self) -> u64 {
+                    self.1 += 1;
+                    rand::RngCore::next_u64(&mut self.0)
+                }
+                fn fill_bytes(&mut self, dest: &mut [u8]) {
+                    self.1 += 1;
+                    rand::RngCore::fill_bytes(&mut self.0, dest)
+                }
+                fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+                    self.fill_bytes(dest);
+                    Ok(())
+                }
+            }
+            use rand::SeedableRng;
+            let mut rng = Counting(rand::rngs::StdRng::seed_from_u64(3), 0);
+            let mut counts = [0; 8];
+            for _ in 0..8000 {
+                counts[Modular::<8>::random(&mut rng).0 as usize] += 1;
+            }
+            assert_eq!(rng.1, 8000);
+            // About 1000 each, with a standard deviation of about 30
+            assert!(
+                counts.iter().all(|&c| (880..1120).contains(&c)),
+                "{:?}",
+                counts
+            );
+            for _ in 0..100 {
+                assert!(Modular::<{ 1 << 31 }>::random(&mut rng).0 < 1 << 31);
+            }
+            assert_eq!(rng.1, 8100);
+            assert!(Modular::<1>::random(&mut rng).is_zero());
+        }
+        #[test]
+        fn test_from_base() {
+            const Q: u32 = 37;
+            let c = Modular::<Q>::from([13]);
+            let x = Modular::<Q>::from([5]);
+            assert_eq!(Modular::from_base(c), c);
+            assert_eq!(Modular::from_base(c) * x, c * x);
+        }
+        #[test]
+        fn test_symbol_round_trip() {
+            const Q: u32 = 3329;
+            const P: u32 = 4;
+            for m in 0..P {
+                let x = Modular::<Q>::encode_symbol::<P>(m);
+                for e in (0..=400).step_by(25) {
+                    let e = Modular::<Q>::from([e]);
+                    assert_eq!((x + e).decode_symbol::<P>(), m);
+                    assert_eq!((x - e).decode_symbol::<P>(), m);
+                }
+            }
+            // Values just below Q round to P, which wraps around to the symbol 0
+            assert_eq!(Modular::<Q>::from([Q - 1]).decode_symbol::<P>(), 0);
+            assert_eq!(Modular::<Q>::encode_symbol::<P>(2), Modular::from([1665]));
+        }
+        #[test]
+        fn test_symbol_round_trip_large() {
+            const Q: u32 = 4294967291;
+            const P: u32 = 1 << 20;
+            for &m in &[0, 1, P / 2, P - 1] {
+                let x = Modular::<Q>::encode_symbol::<P>(m);
+                // Q/P is about 4096, so errors below 2048 decode correctly
+                for &e in &[0, 1, 1000, 2000] {
+                    let e = Modular::<Q>::from([e]);
+                    assert_eq!((x + e).decode_symbol::<P>(), m);
+                    assert_eq!((x - e).decode_symbol::<P>(), m);
+                }
+            }
+            // Here 2 * m * Q and 2 * x * P_BIG both overflow u64
+            const P_BIG: u32 = 4000000000;
+            for &m in &[0, 1, 12345, P_BIG - 1] {
+                let x = Modular::<Q>::encode_symbol::<P_BIG>(m);
+                assert_eq!(x.decode_symbol::<P_BIG>(), m);
+            }
+            // (Q - 1) * P_BIG/Q is P_BIG - 0.93..., which rounds down to the last symbol
+            assert_eq!(
+                Modular::<Q>::from([Q - 1]).decode_symbol::<P_BIG>(),
+                P_BIG - 1
+            );
+        }
+        #[test]
+        #[should_panic(expected = "the plaintext modulus P must be positive")]
+        fn test_symbol_zero_modulus() {
+            Modular::<13>::encode_symbol::<0>(1);
+        }
+        #[test]
+        fn test_from_i128() {
+            const Q: u32 = 1000003;
+            let q: u128 = Q.into();
+            for &x in &[0, 1, -1, 3 << 100, -(7 << 90) - 5, i128::MAX, i128::MIN] {
+                let r = (x.unsigned_abs() % q) as u32;
+                let expected = if x < 0 && r != 0 { Q - r } else { r };
+                assert_eq!(Modular::<Q>::from_i128(x), Modular::from([expected]));
+            }
+            assert_eq!(Modular::<Q>::from_i128(-i128::from(Q)), Modular::zero());
+        }
+        #[test]
+        fn test_sign_magnitude() {
+            const Q: u32 = 13;
+            assert_eq!(Modular::<Q>::from([Q - 2]).sign_magnitude(), (true, 2));
+            assert_eq!(Modular::<Q>::from([6]).sign_magnitude(), (false, 6));
+            assert_eq!(Modular::<Q>::from([7]).sign_magnitude(), (true, 6));
+            assert_eq!(Modular::<Q>::zero().sign_magnitude(), (false, 0));
+        }
+        #[test]
+        fn test_pow_window() {
+            const Q: u32 = 2147483647;
+            let mut state: u64 = 0x9E3779B97F4A7C15;
+            for _ in 0..200 {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let x = Modular::<Q>::from([state as u32]);
+                let exp = state.rotate_left(17) >> (state % 64);
+                for window in 1..=6 {
+                    assert_eq!(x.pow_window(exp, window), x.pow(exp));
+                }
+            }
+            let x = Modular::<Q>::from([3]);
+            for &exp in &[0, 1, 2, 3, 255, 256, 1 << 63, u64::MAX] {
+                assert_eq!(x.pow_window(exp, 4), x.pow(exp));
+                assert_eq!(
+                    Modular::<Q>::zero().pow_window(exp, 4),
+                    Modular::zero().pow(exp)
+                );
+            }
+        }
+        #[test]
+        fn test_pow_wnaf() {
+            let mut state: u64 = 0x9E3779B97F4A7C15;
+            for _ in 0..200 {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let exp = state.rotate_left(17) >> (state % 64);
+                let x = Modular::<2147483647>::from([state as u32]);
+                // Q = 1000 also has non-units (no inverse), which fall back to pow_window
+                let y = Modular::<1000>::from([state as u32]);
+                for window in 2..=6 {
+                    assert_eq!(x.pow_wnaf(exp, window), x.pow(exp));
+                    assert_eq!(y.pow_wnaf(exp, window), y.pow(exp));
+                }
+            }
+            // The NAF of u64::MAX is one digit longer than 64 bits
+            let x = Modular::<12289>::from([3]);
+            assert_eq!(x.pow_wnaf(u64::MAX, 4), x.pow(u64::MAX));
+            assert_eq!(x.pow_wnaf(0, 2), Modular::one());
+            assert_eq!(Modular::<12289>::zero().pow_wnaf(5, 3), Modular::zero());
+        }
+        #[test]
+        #[should_panic(expected = "window 1 is not in 2..=16")]
+        fn test_pow_wnaf_window_too_small() {
+            Modular::<13>::from([2]).pow_wnaf(5, 1);
+        }
+        #[test]
+        fn test_sqrt_and_legendre() {
+            const Q: u32 = 13;
+            let residues = [0, 1, 3, 4, 9, 10, 12];
+            for x in 0..Q {
+                let y = Modular::<Q>::from([x]);
+                let expected = if x == 0 {
+                    0
+                } else if residues.contains(&x) {
+                    1
+                } else {
+                    -1
+                };
+                assert_eq!(y.legendre(), expected);
+                assert_eq!(
+                    y.sqrt().map(|r| r * r),
+                    if expected >= 0 { Some(y) } else { None }
+                );
+            }
+            fn check<const Q: u32>() {
+                for x in (0..Q).step_by(Q as usize / 500 + 1) {
+                    let y = Modular::<Q>::from([x]);
+                    assert_eq!(y.sqrt().is_some(), y.legendre() >= 0);
+                    assert_eq!((y * y).sqrt().map(|r| r * r), Some(y * y));
+                }
+            }
+            check::<2>();
+            check::<11>();
+            check::<12289>();
+            check::<65537>();
+            check::<4294967291>();
+        }
+        #[test]
+        fn test_batch_inverse() {
+            const Q: u32 = 12289;
+            let elems: Vec<_> = (1..=16).map(|i| Modular::<Q>::from([i * 761])).collect();
+            let inverses = Modular::batch_inverse(&elems).unwrap();
+            let expected: Vec<_> = elems.iter().map(|x| x.inverse().unwrap()).collect();
+            assert_eq!(inverses, expected);
+            let mut with_zero = elems.clone();
+            with_zero[7] = Modular::zero();
+            assert_eq!(Modular::batch_inverse(&with_zero), None);
+            // 3 is not a unit mod 12
+            let composite = [1, 5, 3].map(|x| Modular::<12>::from([x]));
+            assert_eq!(Modular::batch_inverse(&composite), None);
+            assert_eq!(Modular::<Q>::batch_inverse(&[]), Some(vec![]));
+        }
+        #[test]
+        fn test_pow() {
+            assert_eq!(Modular::<7>::from([3]).pow(6), Modular::one());
+            assert_eq!(Modular::<7>::zero().pow(0), Modular::one());
+            assert_eq!(Modular::<7>::zero().pow(5), Modular::zero());
+            // 2^100 mod 1000003, computed with Python's pow(2, 100, 1000003)
+            assert_eq!(
+                Modular::<1000003>::from([2]).pow(100),
+                Modular::from([253109])
+            );
+            // Q near 2^31, where the product of two representatives overflows a u32
+            const Q: u32 = 2147483647;
+            let x = Modular::<Q>::from([Q - 1]);
+            assert_eq!(x.pow(2), Modular::one());
+            assert_eq!(x.pow(u64::MAX), x);
+        }
+        #[test]
+        fn test_centered() {
+            const Q: u32 = 13;
+            assert_eq!(Modular::<Q>::from([10]).centered(), -3);
+            assert_eq!(Modular::<Q>::from([6]).centered(), 6);
+            assert_eq!(Modular::<Q>::from(-4i64), Modular::from([9]));
+            assert_eq!(Modular::<Q>::from(-1i64), Modular::from([Q - 1]));
+            assert_eq!(Modular::<Q>::from(i64::MIN).centered(), i64::MIN % 13 + 13);
+            for x in -6..=6i64 {
+                assert_eq!(Modular::<Q>::from(x).centered(), x);
+                assert_eq!(
+                    Modular::<Q>::from(x).centered(),
+                    Modular::<Q>::from(x).representative_near(0)
+                );
+            }
+            assert_eq!(Modular::<16>::from([8]).centered(), 8);
+        }
+        #[test]
+        fn test_representative_near() {
+            const Q: u32 = 13;
+            let x = Modular::<Q>::from([Q / 4]);
+            assert_eq!(x.representative_near(0), 3);
+            assert_eq!(x.representative_near(Q.into()), 3 + 13);
+            assert_eq!(x.representative_near(-100), 3 - 104);
+            // 6 + 13k nearest to 0 and 12
+            assert_eq!(Modular::<Q>::from([6]).representative_near(0), 6);
+            assert_eq!(Modular::<Q>::from([7]).representative_near(0), -6);
+            assert_eq!(Modular::<Q>::from([7]).representative_near(13), 7);
+            // For even Q, ties round up
+            let y = Modular::<16>::from([8]);
+            assert_eq!(y.representative_near(0), 8);
+            assert_eq!(y.representative_near(16), 24);
+            assert_eq!(Modular::<16>::zero().representative_near(8), 16);
+        }
+        #[test]
+        fn test_parity_and_rem_small() {
+            const Q: u32 = 13;
+            let x = Modular::<Q>::from([7]);
+            assert!(x.parity());
+            assert_eq!(x.rem_small(3), 1);
+            // 7 + 7 = 1 mod 13, so the sum of two odd values is odd here
+            assert!((x + x).parity());
+            assert_eq!((x + x).rem_small(5), 1);
+        }
+        #[test]
+        fn test_inverse_prime() {
+            const Q: u32 = 37;
+            for x in 1..Q {
+                let x = Modular::<Q>::from([x]);
+                assert_eq!(x * x.inverse().unwrap(), Modular::one());
+            }
+            assert_eq!(Modular::<Q>::zero().inverse(), None);
+        }
+        #[test]
+        fn test_inverse_composite() {
+            const Q: u32 = 15;
+            for x in 0..Q {
+                let inv = Modular::<Q>::from([x]).inverse();
+                if x % 3 == 0 || x % 5 == 0 {
+                    assert_eq!(inv, None);
+                } else {
+                    assert_eq!(Modular::from([x]) * inv.unwrap(), Modular::one());
+                }
+            }
+        }
+        #[test]
+        fn test_div() {
+            const Q: u32 = 101;
+            let x = Modular::<Q>::from([7]);
+            let y = Modular::<Q>::from([45]);
+            // 45 * 9 = 405 = 1 mod 101
+            assert_eq!(x / y, x * Modular::from([9]));
+            assert_eq!(x / y * y, x);
+            let mut z = x;
+            z /= y;
+            assert_eq!(z, x / y);
+        }
+        #[test]
+        fn test_prime_field() {
+            // Anything bounded on alga's Field works over PrimeModular
+            fn solve_linear<F: Field>(a: F, b: F) -> F {
+                b / a
+            }
+            const Q: u32 = 101;
+            let a = PrimeModular::<Q>::from([45]);
+            let b = PrimeModular::<Q>::from([7]);
+            assert_eq!(a * solve_linear(a, b), b);
+            let a_inv = TwoSidedInverse::<Multiplicative>::two_sided_inverse(&a);
+            assert_eq!(a * a_inv, PrimeModular::one());
+            assert_eq!(
+                Modular::from(b / a),
+                Modular::from([7]) / Modular::from([45])
+            );
+        }
+        #[test]
+        #[should_panic(expected = "division by 0, which is not invertible mod 101")]
+        fn test_div_by_zero() {
+            let _ = PrimeModular::<101>::one() / PrimeModular::zero();
+        }
+        #[test]
+        #[should_panic(expected = "not invertible mod 15")]
+        fn test_div_by_non_unit() {
+            let _ = Modular::<15>::one() / Modular::from([6]);
+        }
+        #[test]
+        fn test_from_rational() {
+            const Q: u32 = 37;
+            let half = Modular::<Q>::from_rational(1, 2).unwrap();
+            assert_eq!(half, Modular::from([2]).inverse().unwrap());
+            assert_eq!(half + half, Modular::one());
+            // -3/4 = (-3) * 28 = -84 = 27 mod 37
+            assert_eq!(
+                Modular::<Q>::from_rational(-3, 4),
+                Some(Modular::from([27]))
+            );
+            assert_eq!(
+                Modular::<Q>::from_rational(6, -2),
+                Some(-Modular::from([3]))
+            );
+            assert_eq!(Modular::<Q>::from_rational(1, 74), None);
+            assert_eq!(Modular::<15>::from_rational(1, 3), None);
+        }
+        #[test]
+        fn test_pow_reduced() {
+            const Q: u32 = 1000003;
+            let x = Modular::<Q>::from([12345]);
+            assert_eq!(x.pow_reduced(u64::from(Q - 1)), Modular::one());
+            assert_eq!(x.pow_reduced(u64::MAX), RingElement::pow(x, u64::MAX));
+            assert_eq!(
+                x.pow_reduced(3 * u64::from(Q - 1) + 7),
+                RingElement::pow(x, 7)
+            );
+            assert_eq!(
+                Modular::<Q>::zero().pow_reduced(u64::from(Q - 1)),
+                Modular::zero()
+            );
+            assert_eq!(Modular::<Q>::zero().pow_reduced(0), Modular::one());
+        }
+        #[test]
+        fn test_le_bytes() {
+            assert_eq!(Modular::<256>::BYTES, 1);
+            assert_eq!(Modular::<257>::BYTES, 2);
+            assert_eq!(Modular::<12289>::BYTES, 2);
+            assert_eq!(Modular::<65537>::BYTES, 3);
+            assert_eq!(Modular::<4294967291>::BYTES, 4);
+            let x = Modular::<12289>::from([12288]);
+            assert_eq!(x.to_le_bytes(), vec![0x00, 0x30]);
+            assert_eq!(Modular::from_le_bytes(&x.to_le_bytes()), Some(x));
+            let y = Modular::<40961>::from([40960]);
+            assert_eq!(Modular::from_le_bytes(&y.to_le_bytes()), Some(y));
+            // 12289 itself, and wrong lengths
+            assert_eq!(Modular::<12289>::from_le_bytes(&[0x01, 0x30]), None);
+            assert_eq!(Modular::<12289>::from_le_bytes(&[0x01]), None);
+            assert_eq!(Modular::<12289>::from_le_bytes(&[0x01, 0x00, 0x00]), None);
+        }
+        #[test]
+        fn test_deserialize_strict() {
+            const Q: u32 = 13;
+            assert_eq!(
+                Modular::<Q>::deserialize_strict(Q - 1),
+                Ok(Modular::from([Q - 1]))
+            );
+            assert_eq!(
+                Modular::<Q>::deserialize_strict(Q),
+                Err(NonCanonical {
+                    value: Q,
+                    modulus: Q
+                })
+            );
+            assert!(Modular::<Q>::deserialize_strict(u32::MAX).is_err());
+            assert_eq!(Modular::<Q>::deserialize_lenient(Q), Modular::zero());
+        }
+        #[test]
+        fn test_mul_matches_naive() {
+            fn check<const Q: u32>() {
+                let mut x: u64 = 0x2545F4914F6CDD1D;
+                for _ in 0..10000 {
+                    x ^= x << 13;
+                    x ^= x >> 7;
+                    x ^= x << 17;
+                    let (a, b) = ((x as u32) % Q, ((x >> 32) as u32) % Q);
+                    let naive = (u64::from(a) * u64::from(b) % u64::from(Q)) as u32;
+                    let sum = (u64::from(a) + u64::from(b)) % u64::from(Q);
+                    let (a, b) = (Modular::<Q>::from([a]), Modular::<Q>::from([b]));
+                    assert_eq!(a * b, Modular::from([naive]));
+                    assert_eq!(a + b, Modular::from([sum as u32]));
+                }
+            }
+            check::<12289>();
+            // Large enough that products overflow a u32, taking the Barrett path
+            check::<8380417>();
+            check::<2147483647>();
+            check::<4294967291>();
+        }
+        #[test]
+        fn test_mul_add() {
+            fn check<const Q: u32>() {
+                let mut x: u64 = 0x9E3779B97F4A7C15;
+                for _ in 0..10000 {
+                    x ^= x << 13;
+                    x ^= x >> 7;
+                    x ^= x << 17;
+                    let c = Modular::<Q>::from([x as u32]);
+                    let a = Modular::<Q>::from([(x >> 21) as u32]);
+                    let b = Modular::<Q>::from([(x >> 42) as u32]);
+                    assert_eq!(c.mul_add(a, b), c + a * b);
+                }
+                let top = -Modular::<Q>::one();
+                assert_eq!(top.mul_add(top, top), top + top * top);
+            }
+            check::<1>();
+            check::<12289>();
+            check::<4294967291>();
+        }
+        #[test]
+        fn test_table_arithmetic() {
+            fn check<const Q: u32>() {
+                for a in 0..Q {
+                    for b in 0..Q {
+                        let (x, y) = (Modular::<Q>::from([a]), Modular::<Q>::from([b]));
+                        assert_eq!(x.add_table(y), x + y);
+                        assert_eq!(x.mul_table(y), x * y);
+                    }
+                }
+            }
+            check::<1>();
+            check::<2>();
+            check::<13>();
+            check::<255>();
+        }
+        #[test]
+        fn test_barrett_reduce() {
+            fn check<const Q: u32>() {
+                // xorshift64, to get inputs across the whole u64 range
+                let mut x: u64 = 0x9E3779B97F4A7C15;
+                for _ in 0..10000 {
+                    x ^= x << 13;
+                    x ^= x >> 7;
+                    x ^= x << 17;
+                    let q: u64 = Q.into();
+                    assert_eq!(barrett_reduce::<Q>(x), Modular::from([(x % q) as u32]));
+                    let y = x % (q * q);
+                    assert_eq!(barrett_reduce::<Q>(y), Modular::from([(y % q) as u32]));
+                }
+                assert_eq!(
+                    barrett_reduce::<Q>(u64::MAX),
+                    Modular::from([(u64::MAX % u64::from(Q)) as u32])
+                );
+            }
+            check::<1>();
+            check::<3329>();
+            check::<12289>();
+            check::<65536>();
+            check::<2147483647>();
+            check::<4294967291>();
+        }
+        #[test]
+        fn test_const_arithmetic() {
+            const X: Modular<4294967291> = Modular::new_const(u32::MAX).pow_const(1 << 40);
+            let x = Modular::<4294967291>::from([u32::MAX]);
+            assert_eq!(X, x.pow(1 << 40));
+            assert_eq!(x.mul_const(x), x * x);
+            assert_eq!(Modular::<13>::new_const(40).val(), 1);
+            assert!(Modular::<13>::new_const(5).pow_const(0).is_one());
+            assert!(Modular::<1>::new_const(1).pow_const(0).is_zero());
+        }
+    }
+}
+
+/// Z/qZ for a modulus only known at runtime (e.g. parsed from the command line), with the
+/// same arithmetic as Modular. Every element carries its modulus, and operations on
+/// elements of different moduli panic.
+///
+/// Zero and One from num_traits are not implemented, as they would have to produce an
+/// element without knowing its modulus; use ModularDyn::zero and ModularDyn::one instead.
+pub mod modular_dyn {
+    use super::*;
+
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub struct ModularDyn {
+        val: u32,
+        modulus: u32,
+    }
+
+    impl ModularDyn {
+        /// val mod modulus. Panics if the modulus is 0.
+        pub fn new(val: u32, modulus: u32) -> Self {
+            assert!(modulus > 0, "the modulus must be positive");
+            ModularDyn {
+                val: val % modulus,
+                modulus,
+            }
+        }
+
+        pub fn zero(modulus: u32) -> Self {
+            Self::new(0, modulus)
+        }
+
+        pub fn one(modulus: u32) -> Self {
+            Self::new(1, modulus)
+        }
+
+        /// The representative in [0, modulus)
+        pub fn val(&self) -> u32 {
+            self.val
+        }
+
+        pub fn modulus(&self) -> u32 {
+            self.modulus
+        }
+
+        pub fn is_zero(&self) -> bool {
+            self.val == 0
+        }
+
+        pub fn is_one(&self) -> bool {
+            self.val == 1 % self.modulus
+        }
+
+        /// The common modulus of self and other, panicking if they differ.
+        fn check_modulus(&self, other: &Self) -> u64 {
+            assert_eq!(
+                self.modulus, other.modulus,
+                "modulus mismatch: {} vs {}",
+                self.modulus, other.modulus
+            );
+            self.modulus.into()
+        }
+    }
+
+    macro_rules! dyn_opp {
+        ($func:ident, $bound:ident) => {
+            impl $bound<ModularDyn> for ModularDyn {
+                type Output = ModularDyn;
+                #[allow(clippy::suspicious_arithmetic_impl)]
+                fn $func(self, other: Self) -> Self::Output {
+                    let modulus = self.check_modulus(&other);
+                    let x: u64 = self.val.into();
+                    let y: u64 = other.val.into();
+                    ModularDyn {
+                        val: (u64::$func(x, y) % modulus) as u32,
+                        modulus: self.modulus,
+                    }
+                }
+            }
+        };
+    }
+    dyn_opp!(add, Add);
+    dyn_opp!(mul, Mul);
+
+    impl Neg for ModularDyn {
+        type Output = ModularDyn;
+        fn neg(self) -> Self::Output {
+            ModularDyn::new(self.modulus - self.val, self.modulus)
+        }
+    }
+
+    impl Sub<ModularDyn> for ModularDyn {
+        type Output = ModularDyn;
+        #[allow(clippy::suspicious_arithmetic_impl)]
+        fn sub(self, other: Self) -> Self::Output {
+            self.check_modulus(&other);
+            self + other.neg()
+        }
+    }
+
+    macro_rules! dyn_op_assign {
+        ($func:ident, $bound:ident, $method:ident) => {
+            impl $bound<ModularDyn> for ModularDyn {
+                fn $func(&mut self, other: Self) {
+                    *self = self.$method(other)
+                }
+            }
+        };
+    }
+    dyn_op_assign!(add_assign, AddAssign, add);
+    dyn_op_assign!(mul_assign, MulAssign, mul);
+    dyn_op_assign!(sub_assign, SubAssign, sub);
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        #[test]
+        fn test_new() {
+            let x = ModularDyn::new(30, 13);
+            assert_eq!(x.val(), 4);
+            assert_eq!(x.modulus(), 13);
+            assert!(ModularDyn::zero(13).is_zero());
+            assert!(ModularDyn::one(13).is_one());
+            assert!(ModularDyn::one(1).is_zero() && ModularDyn::one(1).is_one());
+        }
+        #[test]
+        fn test_add_zero() {
+            let x = ModularDyn::new(5, 27);
+            let y = ModularDyn::zero(27);
+            assert_eq!(x + y, x);
+            assert_eq!(y + x, x);
+        }
+        #[test]
+        fn test_sub_and_neg() {
+            const Q: u32 = 31;
+            let x = ModularDyn::new(5, Q);
+            let y = ModularDyn::new(6, Q);
+            let z = ModularDyn::one(Q);
+            assert_eq!(x - y, ModularDyn::new(Q - 1, Q));
+            assert_eq!(x - y, -z);
+            assert_eq!(-ModularDyn::zero(Q), ModularDyn::zero(Q));
+        }
+        #[test]
+        fn test_mul() {
+            let x = ModularDyn::new(13, 37);
+            let y = ModularDyn::new(5, 37);
+            let mut z = x;
+            z *= y;
+            assert_eq!(z, ModularDyn::new(28, 37));
+            // Products that overflow a u32
+            let big = ModularDyn::new(u32::MAX - 5, u32::MAX - 4);
+            assert_eq!(big * big, ModularDyn::one(u32::MAX - 4));
+        }
+        #[test]
+        fn test_matches_modular() {
+            use super::modular::Modular;
+            const Q: u32 = 12289;
+            for &(a, b) in &[(0, 0), (1, Q - 1), (5000, 9000), (Q - 1, Q - 1)] {
+                let (x, y) = (Modular::<Q>::from([a]), Modular::<Q>::from([b]));
+                let (u, v) = (ModularDyn::new(a, Q), ModularDyn::new(b, Q));
+                assert_eq!((u + v).val(), (x + y).to_coeffs()[0]);
+                assert_eq!((u - v).val(), (x - y).to_coeffs()[0]);
+                assert_eq!((u * v).val(), (x * y).to_coeffs()[0]);
+            }
+        }
+        #[test]
+        #[should_panic(expected = "modulus mismatch: 13 vs 17")]
+        fn test_modulus_mismatch() {
+            let _ = ModularDyn::new(1, 13) + ModularDyn::new(1, 17);
+        }
     }
 }
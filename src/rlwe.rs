@@ -0,0 +1,724 @@
+//! Textbook public-key RLWE encryption (Lyubashevsky-Peikert-Regev) of N-bit messages,
+//! over R_q = (Z/QZ)[x]/(x^N + 1) with discrete Gaussian secrets and errors.
+//!
+//! A message is a polynomial with 0/1 coefficients, encoded as floor(Q/2) * m. Decryption
+//! recovers v - u * s = floor(Q/2) * m + (e * r + e2 - s * e1), and succeeds as long as
+//! every coefficient of the noise in parentheses is below Q/4 in absolute value.
+
+use crate::poly::ntt::NttContext;
+use crate::poly::Poly;
+use crate::rings::modular::Modular;
+use crate::sampling::sample_gaussian_poly;
+use alga::general::RingCommutative;
+use num_traits::identities::{One, Zero};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::array;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SecretKey<const Q: u32, const N: usize> {
+    pub s: Poly<Modular<Q>, N>,
+}
+
+impl<const Q: u32, const N: usize> SecretKey<Q, N> {
+    /// The same small secret mod Q2, lifting each coefficient's centered representative,
+    /// to decrypt ciphertexts rescaled to Q2.
+    pub fn switch_modulus<const Q2: u32>(&self) -> SecretKey<Q2, N> {
+        SecretKey {
+            s: self.s.map_coeffs(|c| Modular::from(c.centered())),
+        }
+    }
+}
+
+/// (a, b = a * s + e), along with the sigma that encryption samples its noise with.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PublicKey<const Q: u32, const N: usize> {
+    pub a: Poly<Modular<Q>, N>,
+    pub b: Poly<Modular<Q>, N>,
+    pub sigma: f64,
+}
+
+/// (u, v), along with an estimate of the variance of each coefficient of its decryption
+/// noise, which homomorphic operations update.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Ciphertext<const Q: u32, const N: usize> {
+    pub u: Poly<Modular<Q>, N>,
+    pub v: Poly<Modular<Q>, N>,
+    pub noise_variance: f64,
+}
+
+/// How many standard deviations of noise the budget keeps below Q/4. A Gaussian exceeds 6
+/// standard deviations with probability about 2^-28.
+const NOISE_TAIL: f64 = 6.0;
+
+impl<const Q: u32, const N: usize> Ciphertext<Q, N> {
+    /// The bits of headroom left: log2 of Q/4 over NOISE_TAIL standard deviations of the
+    /// estimated noise. Decryption is likely to start failing once this is negative.
+    pub fn noise_budget(&self) -> f64 {
+        (f64::from(Q / 4) / (NOISE_TAIL * self.noise_variance.sqrt())).log2()
+    }
+
+    /// Rescales (modulus switches) to the next modulus Q2 of a chain, typically Q2 < Q,
+    /// by rounding every coefficient c to c * Q2/Q. The message term then becomes about
+    /// floor(Q2/2) * m, so the result decrypts under sk.switch_modulus::<Q2>().
+    ///
+    /// The noise scales by Q2/Q, plus the rounding error r_v - r_u * s with r uniform in
+    /// [-1/2, 1/2], of variance (1 + N secret_sigma^2) / 12 for the parameter secret_sigma
+    /// the secret was sampled with (keygen's sigma).
+    pub fn rescale<const Q2: u32>(&self, secret_sigma: f64) -> Ciphertext<Q2, N> {
+        let round = |c: Modular<Q>| {
+            // floor(c * Q2/Q + 1/2) on the centered representative
+            let x = 2 * i128::from(c.centered()) * i128::from(Q2) + i128::from(Q);
+            Modular::from_i128(x.div_euclid(2 * i128::from(Q)))
+        };
+        let ratio = f64::from(Q2) / f64::from(Q);
+        let rounding = (1.0 + N as f64 * secret_sigma * secret_sigma) / 12.0;
+        Ciphertext {
+            u: self.u.map_coeffs(round),
+            v: self.v.map_coeffs(round),
+            noise_variance: self.noise_variance * ratio * ratio + rounding,
+        }
+    }
+
+    /// Noise flooding: adds a fresh discrete Gaussian with parameter sigma to every
+    /// coefficient of v, smudging the existing noise (e.g. for circuit privacy) while the
+    /// message survives as long as the budget allows. The variance grows by sigma^2.
+    pub fn add_noise<G: Rng + ?Sized>(&mut self, sigma: f64, rng: &mut G) {
+        self.v += sample_gaussian_poly(rng, sigma);
+        self.noise_variance += sigma * sigma;
+    }
+}
+
+// Byte encodings. Each starts with Q and N as little-endian u32s, which decoding checks
+// against the target type, followed by the polynomials' Poly::to_le_bytes encodings and
+// then any f64 parameters as little-endian IEEE 754.
+
+fn encode_header<const Q: u32, const N: usize>() -> Vec<u8> {
+    let mut out = Q.to_le_bytes().to_vec();
+    out.extend_from_slice(&(N as u32).to_le_bytes());
+    out
+}
+
+/// Splits bytes into its polynomials and f64s, or None if the header names other
+/// parameters, the length is not exactly that of `polys` polynomials and `floats` f64s, or
+/// a coefficient is not canonical.
+fn decode_body<const Q: u32, const N: usize>(
+    bytes: &[u8],
+    polys: usize,
+    floats: usize,
+) -> Option<(Vec<Poly<Modular<Q>, N>>, Vec<f64>)> {
+    let header = encode_header::<Q, N>();
+    let width = N * Modular::<Q>::BYTES;
+    if bytes.len() != header.len() + polys * width + floats * 8 || !bytes.starts_with(&header) {
+        return None;
+    }
+    let (poly_bytes, float_bytes) = bytes[header.len()..].split_at(polys * width);
+    let polys = poly_bytes
+        .chunks_exact(width.max(1))
+        .take(polys)
+        .map(Poly::from_le_bytes)
+        .collect::<Option<Vec<_>>>()?;
+    let floats = float_bytes
+        .chunks_exact(8)
+        .map(|chunk| f64::from_le_bytes(array::from_fn(|i| chunk[i])))
+        .collect();
+    Some((polys, floats))
+}
+
+impl<const Q: u32, const N: usize> SecretKey<Q, N> {
+    /// The header followed by s.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = encode_header::<Q, N>();
+        out.extend(self.s.to_le_bytes());
+        out
+    }
+
+    /// Inverse of to_bytes, or None if the length, Q or N do not match or a coefficient is
+    /// not canonical.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (polys, _) = decode_body::<Q, N>(bytes, 1, 0)?;
+        Some(SecretKey { s: polys[0] })
+    }
+}
+
+impl<const Q: u32, const N: usize> PublicKey<Q, N> {
+    /// The header followed by a, b and sigma.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = encode_header::<Q, N>();
+        out.extend(self.a.to_le_bytes());
+        out.extend(self.b.to_le_bytes());
+        out.extend_from_slice(&self.sigma.to_le_bytes());
+        out
+    }
+
+    /// Inverse of to_bytes, or None if the length, Q or N do not match, a coefficient is
+    /// not canonical, or sigma is not a positive finite number.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (polys, floats) = decode_body::<Q, N>(bytes, 2, 1)?;
+        let sigma = floats[0];
+        if !(sigma.is_finite() && sigma > 0.0) {
+            return None;
+        }
+        Some(PublicKey {
+            a: polys[0],
+            b: polys[1],
+            sigma,
+        })
+    }
+}
+
+impl<const Q: u32, const N: usize> Ciphertext<Q, N> {
+    /// The header followed by u, v and noise_variance.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = encode_header::<Q, N>();
+        out.extend(self.u.to_le_bytes());
+        out.extend(self.v.to_le_bytes());
+        out.extend_from_slice(&self.noise_variance.to_le_bytes());
+        out
+    }
+
+    /// Inverse of to_bytes, or None if the length, Q or N do not match, a coefficient is
+    /// not canonical, or the noise variance is negative or not finite.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (polys, floats) = decode_body::<Q, N>(bytes, 2, 1)?;
+        let noise_variance = floats[0];
+        if !(noise_variance.is_finite() && noise_variance >= 0.0) {
+            return None;
+        }
+        Some(Ciphertext {
+            u: polys[0],
+            v: polys[1],
+            noise_variance,
+        })
+    }
+}
+
+/// Standard (padded) base64 of the to_bytes encodings, behind the base64 feature, e.g.
+/// for pasting known-answer test vectors. Decoding is None for invalid base64 or whenever
+/// from_bytes is.
+#[cfg(feature = "base64")]
+macro_rules! base64_codec {
+    ($type:ident) => {
+        impl<const Q: u32, const N: usize> $type<Q, N> {
+            /// to_bytes, in standard base64.
+            pub fn to_base64(&self) -> String {
+                use base64::Engine;
+                base64::engine::general_purpose::STANDARD.encode(self.to_bytes())
+            }
+
+            /// Inverse of to_base64.
+            pub fn from_base64(encoded: &str) -> Option<Self> {
+                use base64::Engine;
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(encoded)
+                    .ok()?;
+                Self::from_bytes(&bytes)
+            }
+        }
+    };
+}
+#[cfg(feature = "base64")]
+base64_codec!(SecretKey);
+#[cfg(feature = "base64")]
+base64_codec!(PublicKey);
+#[cfg(feature = "base64")]
+base64_codec!(Ciphertext);
+
+/// A fresh key pair: uniform a, and s and e discrete Gaussian with parameter sigma.
+pub fn keygen<G: Rng + ?Sized, const Q: u32, const N: usize>(
+    rng: &mut G,
+    sigma: f64,
+) -> (SecretKey<Q, N>, PublicKey<Q, N>) {
+    let a = Poly::random(rng);
+    let s = sample_gaussian_poly(rng, sigma);
+    let e = sample_gaussian_poly(rng, sigma);
+    (
+        SecretKey { s },
+        PublicKey {
+            a,
+            b: a * s + e,
+            sigma,
+        },
+    )
+}
+
+/// keygen with all randomness (a, s and e) drawn from a ChaCha-based StdRng seeded with
+/// seed, so the same seed always gives the same key pair. StdRng's algorithm is not
+/// guaranteed across rand releases, so keys are reproducible for a fixed rand version but
+/// are not suitable as long-lived known-answer tests.
+pub fn keygen_from_seed<const Q: u32, const N: usize>(
+    seed: &[u8; 32],
+    sigma: f64,
+) -> (SecretKey<Q, N>, PublicKey<Q, N>) {
+    keygen(&mut StdRng::from_seed(*seed), sigma)
+}
+
+/// Encrypts a message with 0/1 coefficients as (u, v) = (a * r + e1, b * r + e2 +
+/// floor(Q/2) * m), for fresh Gaussian r, e1 and e2. Panics if the message is not binary.
+///
+/// Each coefficient of the noise e * r + e2 - s * e1 is a sum of 2N products of two
+/// Gaussians plus one Gaussian, so has variance 2N sigma^4 + sigma^2.
+pub fn encrypt<G: Rng + ?Sized, const Q: u32, const N: usize>(
+    rng: &mut G,
+    pk: &PublicKey<Q, N>,
+    message: &Poly<Modular<Q>, N>,
+) -> Ciphertext<Q, N> {
+    assert!(
+        message.0.iter().all(|c| c.is_zero() || c.is_one()),
+        "messages must have 0/1 coefficients"
+    );
+    let half = Modular::from([Q / 2]);
+    let r = sample_gaussian_poly(rng, pk.sigma);
+    let e1 = sample_gaussian_poly(rng, pk.sigma);
+    let e2 = sample_gaussian_poly(rng, pk.sigma);
+    let variance = pk.sigma * pk.sigma;
+    Ciphertext {
+        u: pk.a * r + e1,
+        v: pk.b * r + e2 + Poly(message.0.map(|c| c * half)),
+        noise_variance: 2.0 * N as f64 * variance * variance + variance,
+    }
+}
+
+/// The sum of the ciphertexts, accumulated in place into one ciphertext, which encrypts
+/// the sum of the messages mod 2 (coefficientwise XOR). The noises add, and treating them
+/// as independent so do their variances, so the variance grows linearly in the number of
+/// ciphertexts. The sum of no ciphertexts is the noiseless encryption of 0.
+pub fn sum_ciphertexts<const Q: u32, const N: usize>(cts: &[Ciphertext<Q, N>]) -> Ciphertext<Q, N> {
+    let mut sum = Ciphertext {
+        u: Poly::zero(),
+        v: Poly::zero(),
+        noise_variance: 0.0,
+    };
+    for ct in cts {
+        sum.u += ct.u;
+        sum.v += ct.v;
+        sum.noise_variance += ct.noise_variance;
+    }
+    sum
+}
+
+/// Rounds each coefficient of v - u * s to whichever of 0 and floor(Q/2) is nearer.
+pub fn decrypt<const Q: u32, const N: usize>(
+    sk: &SecretKey<Q, N>,
+    ct: &Ciphertext<Q, N>,
+) -> Poly<Modular<Q>, N> {
+    round_message(&(ct.v - ct.u * sk.s))
+}
+
+fn round_message<const Q: u32, const N: usize>(noisy: &Poly<Modular<Q>, N>) -> Poly<Modular<Q>, N> {
+    Poly(noisy.0.map(|c| {
+        if c.centered().unsigned_abs() > u64::from(Q / 4) {
+            Modular::one()
+        } else {
+            Modular::zero()
+        }
+    }))
+}
+
+/// Whether each ciphertext decrypts to the message at the same index of expected. The
+/// secret is transformed into the NTT domain once, so each ciphertext costs one forward
+/// and one inverse transform rather than a schoolbook product. Panics if the slices have
+/// different lengths, or if Q and N do not support NttContext.
+pub fn batch_decrypt_verify<const Q: u32, const N: usize>(
+    sk: &SecretKey<Q, N>,
+    cts: &[Ciphertext<Q, N>],
+    expected: &[Poly<Modular<Q>, N>],
+) -> Vec<bool> {
+    assert_eq!(
+        cts.len(),
+        expected.len(),
+        "one expected message per ciphertext"
+    );
+    let ntt = NttContext::<Q, N>::new();
+    let mut s_hat = sk.s;
+    ntt.forward(&mut s_hat);
+    cts.iter()
+        .zip(expected)
+        .map(|(ct, m)| {
+            let mut us = ct.u;
+            ntt.forward(&mut us);
+            us = Poly(array::from_fn(|i| us.0[i] * s_hat.0[i]));
+            ntt.inverse(&mut us);
+            round_message(&(ct.v - us)) == *m
+        })
+        .collect()
+}
+
+/// The fraction of message bits decrypted wrongly over `trials` encryptions of random
+/// messages, each under a fresh key pair. For sanity-checking parameters: a useful
+/// scheme should see 0 here for any feasible number of trials.
+pub fn decryption_error_rate<G: Rng + ?Sized, const Q: u32, const N: usize>(
+    rng: &mut G,
+    sigma: f64,
+    trials: usize,
+) -> f64 {
+    let mut errors = 0;
+    for _ in 0..trials {
+        let (sk, pk) = keygen::<_, Q, N>(rng, sigma);
+        let message = Poly(array::from_fn(|_| {
+            Modular::from([rng.gen::<bool>() as u32])
+        }));
+        let decrypted = decrypt(&sk, &encrypt(rng, &pk, &message));
+        errors += (0..N).filter(|&i| decrypted.0[i] != message.0[i]).count();
+    }
+    errors as f64 / (trials * N) as f64
+}
+
+/// Predicts the decryption noise of a circuit over Ciphertext<Q, N> before running it, as
+/// both a worst-case bound on every coefficient and a heuristic average (the variance of
+/// each coefficient, treating noise terms as independent). Worst cases use the sampler's
+/// 12 sigma tail cut as the bound on each Gaussian coefficient.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NoiseTracker<const Q: u32, const N: usize> {
+    /// A bound on the absolute value of every noise coefficient.
+    pub worst: f64,
+    /// The estimated variance of each noise coefficient, as Ciphertext::noise_variance.
+    pub variance: f64,
+    sigma: f64,
+}
+
+impl<const Q: u32, const N: usize> NoiseTracker<Q, N> {
+    /// A fresh encryption under noise parameter sigma: e r + e2 - s e1 has coefficients of
+    /// at most 2N B^2 + B for B the tail cut, and variance 2N sigma^4 + sigma^2 (as in
+    /// encrypt).
+    pub fn fresh(sigma: f64) -> Self {
+        let bound = (crate::sampling::TAIL_CUT * sigma).ceil();
+        let n = N as f64;
+        NoiseTracker {
+            worst: 2.0 * n * bound * bound + bound,
+            variance: 2.0 * n * sigma.powi(4) + sigma * sigma,
+            sigma,
+        }
+    }
+
+    /// Homomorphic addition: the noises add.
+    pub fn add(&self, other: &Self) -> Self {
+        NoiseTracker {
+            worst: self.worst + other.worst,
+            variance: self.variance + other.variance,
+            sigma: self.sigma,
+        }
+    }
+
+    /// Multiplication by a known plaintext polynomial p, which multiplies the noise by p:
+    /// each coefficient is a signed sum of products with p's coefficients, so is bounded by
+    /// ||p||_1 times the old bound and has ||p||_2^2 times the variance.
+    pub fn mul_plain(&self, p: &Poly<Modular<Q>, N>) -> Self {
+        let l1: u64 = p.0.iter().map(|c| c.centered().unsigned_abs()).sum();
+        NoiseTracker {
+            worst: self.worst * l1 as f64,
+            variance: self.variance * p.norm_l2_squared() as f64,
+            sigma: self.sigma,
+        }
+    }
+
+    /// An automorphism x -> x^k (a slot rotation), which permutes the noise coefficients
+    /// up to sign, so changes neither estimate.
+    pub fn rotate(&self) -> Self {
+        *self
+    }
+
+    /// Ciphertext::rescale to Q2: the noise scales by Q2/Q, plus the rounding error
+    /// r_v - r_u s, bounded by (1 + N B)/2 for B the bound on the secret's coefficients.
+    pub fn rescale<const Q2: u32>(&self) -> NoiseTracker<Q2, N> {
+        let ratio = f64::from(Q2) / f64::from(Q);
+        let bound = (crate::sampling::TAIL_CUT * self.sigma).ceil();
+        let n = N as f64;
+        NoiseTracker {
+            worst: self.worst * ratio + (1.0 + n * bound) / 2.0,
+            variance: self.variance * ratio * ratio + (1.0 + n * self.sigma * self.sigma) / 12.0,
+            sigma: self.sigma,
+        }
+    }
+
+    /// Whether decryption is guaranteed, i.e. the worst case stays below Q/4.
+    pub fn always_decrypts(&self) -> bool {
+        self.worst < f64::from(Q / 4)
+    }
+
+    /// The heuristic headroom in bits, as Ciphertext::noise_budget.
+    pub fn average_budget(&self) -> f64 {
+        (f64::from(Q / 4) / (NOISE_TAIL * self.variance.sqrt())).log2()
+    }
+}
+
+/// The BFV-style tensor of two ciphertext pairs, (c0.0 c1.0, c0.0 c1.1 + c0.1 c1.0,
+/// c0.1 c1.1). If c0 and c1 decrypt linearly under s (c.0 + c.1 s = m), the result
+/// decrypts to the product m0 m1 against (1, s, s^2). Works over any commutative ring,
+/// e.g. Modular or Poly.
+pub fn tensor<P: RingCommutative + Copy>(c0: &(P, P), c1: &(P, P)) -> (P, P, P) {
+    (c0.0 * c1.0, c0.0 * c1.1 + c0.1 * c1.0, c0.1 * c1.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const Q: u32 = 12289;
+    const N: usize = 256;
+    const SIGMA: f64 = 3.2;
+    #[test]
+    fn test_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let (sk, pk) = keygen::<_, Q, N>(&mut rng, SIGMA);
+        for _ in 0..5 {
+            let message = Poly(array::from_fn(|_| {
+                Modular::from([rng.gen::<bool>() as u32])
+            }));
+            let ct = encrypt(&mut rng, &pk, &message);
+            assert_ne!(ct.v, message);
+            assert_eq!(decrypt(&sk, &ct), message);
+        }
+    }
+    #[test]
+    fn test_decryption_error_rate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(decryption_error_rate::<_, Q, N>(&mut rng, SIGMA, 4), 0.0);
+        // Noise this large swamps Q/4, so about half the bits come out wrong
+        let rate = decryption_error_rate::<_, Q, 16>(&mut rng, 1000.0, 20);
+        assert!(rate > 0.3 && rate < 0.7, "error rate {}", rate);
+    }
+    #[test]
+    fn test_sum_ciphertexts() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let (sk, pk) = keygen::<_, Q, N>(&mut rng, SIGMA);
+        let messages: Vec<Poly<Modular<Q>, N>> = (0..4)
+            .map(|_| {
+                Poly(array::from_fn(|_| {
+                    Modular::from([rng.gen::<bool>() as u32])
+                }))
+            })
+            .collect();
+        let cts: Vec<_> = messages.iter().map(|m| encrypt(&mut rng, &pk, m)).collect();
+        let sum = sum_ciphertexts(&cts);
+        let expected = Poly(array::from_fn(|i| {
+            let ones = messages.iter().filter(|m| m.0[i].is_one()).count();
+            Modular::from([ones as u32 % 2])
+        }));
+        assert_eq!(decrypt(&sk, &sum), expected);
+        // Four times the variance is one bit less budget
+        let fresh = cts[0].noise_budget();
+        assert!(fresh > 0.0);
+        assert!((fresh - sum.noise_budget() - 1.0).abs() < 1e-9);
+        assert!(decrypt(&sk, &sum_ciphertexts::<Q, N>(&[])).is_zero());
+    }
+    #[test]
+    fn test_add_noise() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let (sk, pk) = keygen::<_, Q, N>(&mut rng, SIGMA);
+        let message = Poly(array::from_fn(|_| {
+            Modular::from([rng.gen::<bool>() as u32])
+        }));
+        let mut ct = encrypt(&mut rng, &pk, &message);
+        let (fresh, fresh_budget) = (ct, ct.noise_budget());
+        ct.add_noise(100.0, &mut rng);
+        assert_ne!(ct.v, fresh.v);
+        assert_eq!(ct.u, fresh.u);
+        assert_eq!(ct.noise_variance, fresh.noise_variance + 100.0 * 100.0);
+        assert!(ct.noise_budget() < fresh_budget);
+        assert_eq!(decrypt(&sk, &ct), message);
+    }
+    #[test]
+    fn test_rescale() {
+        // A two-step chain 12289 -> 7681 -> 3329
+        let mut rng = StdRng::seed_from_u64(5);
+        let (sk, pk) = keygen::<_, Q, N>(&mut rng, SIGMA);
+        let bits: [u32; N] = array::from_fn(|_| rng.gen::<bool>() as u32);
+        let ct = encrypt(&mut rng, &pk, &Poly::from(bits));
+        let ct1 = ct.rescale::<7681>(SIGMA);
+        let sk1 = sk.switch_modulus::<7681>();
+        assert_eq!(decrypt(&sk1, &ct1), Poly::from(bits));
+        // The message term is now floor(7681/2) * m, up to the scaled noise
+        let noise = ct1.v - ct1.u * sk1.s - Poly::from(bits).scale(Modular::from([7681 / 2]));
+        assert!(noise.norm_inf() < 7681 / 4);
+        let ct2 = ct1.rescale::<3329>(SIGMA);
+        assert_eq!(
+            decrypt(&sk1.switch_modulus::<3329>(), &ct2),
+            Poly::from(bits)
+        );
+        assert!(ct2.noise_variance < ct1.noise_variance);
+        assert!(ct1.noise_variance < ct.noise_variance);
+        assert!(ct2.noise_budget() > 0.0);
+    }
+    #[test]
+    fn test_batch_decrypt_verify() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let (sk, pk) = keygen::<_, Q, N>(&mut rng, SIGMA);
+        let messages: Vec<Poly<Modular<Q>, N>> = (0..6)
+            .map(|k| Poly::from(array::from_fn(|i| ((i + k) % 5 == 0) as u32)))
+            .collect();
+        let mut cts: Vec<_> = messages.iter().map(|m| encrypt(&mut rng, &pk, m)).collect();
+        // Flip a bit of ciphertext 1, push ciphertext 3 past the noise bound, and swap the
+        // expected message of ciphertext 4
+        cts[1].v.0[7] += Modular::from([Q / 2]);
+        cts[3].v.0[0] += Modular::from([Q / 4]);
+        cts[3].v.0[0] += Modular::from([Q / 4]);
+        let mut expected = messages.clone();
+        expected[4] = messages[0];
+        let flags = batch_decrypt_verify(&sk, &cts, &expected);
+        assert_eq!(flags, [true, false, true, false, false, true]);
+        for ((ct, m), flag) in cts.iter().zip(&expected).zip(flags) {
+            assert_eq!(decrypt(&sk, ct) == *m, flag);
+        }
+        assert!(batch_decrypt_verify(&sk, &[], &[]).is_empty());
+    }
+    #[test]
+    fn test_keygen_from_seed() {
+        let seed = array::from_fn(|i| i as u8);
+        let (sk, pk) = keygen_from_seed::<Q, N>(&seed, SIGMA);
+        assert_eq!(keygen_from_seed(&seed, SIGMA), (sk, pk));
+        let mut other = seed;
+        other[31] ^= 1;
+        let (sk2, pk2) = keygen_from_seed::<Q, N>(&other, SIGMA);
+        assert_ne!(sk2, sk);
+        assert_ne!(pk2.a, pk.a);
+        // The derived keys work
+        let m = Poly::from(array::from_fn(|i| (i % 3 == 0) as u32));
+        let mut rng = StdRng::seed_from_u64(10);
+        assert_eq!(decrypt(&sk, &encrypt(&mut rng, &pk, &m)), m);
+    }
+    #[test]
+    fn test_noise_tracker() {
+        // The circuit (ct0 + ct1) * p, rotated by x -> x^3, then rescaled to 7681. The
+        // actual noise is that of each decryption, carried through the same operations.
+        fn automorphism(p: &Poly<Modular<Q>, N>, k: usize) -> Poly<Modular<Q>, N> {
+            let mut out = Poly::zero();
+            for (i, c) in p.0.iter().enumerate() {
+                let j = i * k % (2 * N);
+                if j < N {
+                    out.0[j] += *c;
+                } else {
+                    out.0[j - N] -= *c;
+                }
+            }
+            out
+        }
+        let mut rng = StdRng::seed_from_u64(9);
+        let (sk, pk) = keygen::<_, Q, N>(&mut rng, SIGMA);
+        let half = Modular::from([Q / 2]);
+        let noise =
+            |ct: &Ciphertext<Q, N>, m: &Poly<Modular<Q>, N>| ct.v - ct.u * sk.s - m.scale(half);
+        let (m0, m1) = (Poly::zero(), Poly::zero());
+        let (ct0, ct1) = (encrypt(&mut rng, &pk, &m0), encrypt(&mut rng, &pk, &m1));
+        let p = Poly::from(array::from_fn(|i| {
+            [1, 0, Q - 1, 0, 0, 2][i % 6] * (i < 3) as u32
+        }));
+        let actual = automorphism(&((noise(&ct0, &m0) + noise(&ct1, &m1)) * p), 3);
+        let fresh = NoiseTracker::<Q, N>::fresh(SIGMA);
+        let predicted = fresh.add(&fresh).mul_plain(&p).rotate();
+        assert_eq!(predicted.variance, 2.0 * ct0.noise_variance * 2.0);
+        // The worst case bounds every coefficient, and the average predicts their spread
+        assert!((actual.norm_inf() as f64) < predicted.worst);
+        let (_, _, std_dev) = actual.noise_profile();
+        let ratio = std_dev / predicted.variance.sqrt();
+        assert!(
+            ratio > 0.8 && ratio < 1.25,
+            "std dev {} predicted {}",
+            std_dev,
+            predicted.variance.sqrt()
+        );
+        // Rescaling a fresh ciphertext, against Ciphertext::rescale
+        let rescaled = ct0.rescale::<7681>(SIGMA);
+        let tracked = fresh.rescale::<7681>();
+        assert!((tracked.variance - rescaled.noise_variance).abs() < 1e-9);
+        let sk1 = sk.switch_modulus::<7681>();
+        let actual = rescaled.v - rescaled.u * sk1.s;
+        assert!((actual.norm_inf() as f64) < tracked.worst);
+        assert!(tracked.average_budget() > 0.0);
+        // The worst case is far more pessimistic than the average
+        assert!(!fresh.always_decrypts());
+        assert!(NoiseTracker::<Q, 4>::fresh(0.5).always_decrypts());
+    }
+    #[test]
+    fn test_tensor() {
+        // Linear encryptions (m - a s, a) of m under s, decrypting as c.0 + c.1 s
+        fn check<P: RingCommutative + Copy + std::fmt::Debug>(s: P, m: [P; 2], a: [P; 2]) {
+            let c0 = (m[0] - a[0] * s, a[0]);
+            let c1 = (m[1] - a[1] * s, a[1]);
+            let (d0, d1, d2) = tensor(&c0, &c1);
+            assert_eq!(d0 + d1 * s + d2 * s * s, m[0] * m[1]);
+        }
+        let m = |x| Modular::<97>::from([x]);
+        check(m(5), [m(3), m(7)], [m(40), m(61)]);
+        let p = Poly::<Modular<Q>, 4>::from;
+        check(
+            p([1, 0, Q - 1, 0]),
+            [p([1, 1, 0, 0]), p([0, 0, 1, 0])],
+            [p([9, 8, 7, 6]), p([100, 0, 5, 12288])],
+        );
+    }
+    #[test]
+    fn test_tensor_noisy() {
+        // BGV-style encryptions (m + T e - a s, a) of m mod T, decrypting as c.0 + c.1 s
+        // with noise T e. The tensor decrypts to (m0 + T e0)(m1 + T e1), which is m0 m1
+        // mod T while the noise stays below Q/2.
+        const T: u32 = 3;
+        const N: usize = 32;
+        let mut rng = StdRng::seed_from_u64(6);
+        let s = sample_gaussian_poly::<_, Q, N>(&mut rng, SIGMA);
+        let m0 = Poly::from(array::from_fn(|_| rng.gen_range(0..T)));
+        let m1 = Poly::from(array::from_fn(|_| rng.gen_range(0..T)));
+        let mut encrypt_mod_t = |m: &Poly<Modular<Q>, N>| {
+            let e = sample_gaussian_poly(&mut rng, SIGMA).scale(Modular::from([T]));
+            let a = Poly::random(&mut rng);
+            (*m + e - a * s, a)
+        };
+        let (c0, c1) = (encrypt_mod_t(&m0), encrypt_mod_t(&m1));
+        let (d0, d1, d2) = tensor(&c0, &c1);
+        let decrypted = d0 + d1 * s + d2 * s * s;
+        assert_ne!(decrypted, m0 * m1);
+        assert!(decrypted.norm_inf() < u64::from(Q / 2));
+        let mod_t = |p: Poly<Modular<Q>, N>| p.centered_coeffs().map(|c| c.rem_euclid(T.into()));
+        assert_eq!(mod_t(decrypted), mod_t(m0 * m1));
+    }
+    #[test]
+    fn test_byte_encodings() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let (sk, pk) = keygen::<_, Q, N>(&mut rng, SIGMA);
+        let ct = encrypt(&mut rng, &pk, &Poly::zero());
+        assert_eq!(SecretKey::from_bytes(&sk.to_bytes()), Some(sk));
+        assert_eq!(PublicKey::from_bytes(&pk.to_bytes()), Some(pk));
+        assert_eq!(Ciphertext::from_bytes(&ct.to_bytes()), Some(ct));
+        // Two bytes per coefficient for Q = 12289, after the 8-byte header
+        let bytes = ct.to_bytes();
+        assert_eq!(bytes.len(), 8 + 2 * 2 * N + 8);
+        assert_eq!(bytes[..8], [0x01, 0x30, 0, 0, 0, 1, 0, 0]);
+        // Truncated, for other parameters, or out of range
+        assert_eq!(
+            Ciphertext::<Q, N>::from_bytes(&bytes[..bytes.len() - 1]),
+            None
+        );
+        assert_eq!(Ciphertext::<7681, N>::from_bytes(&bytes), None);
+        assert_eq!(Ciphertext::<Q, 128>::from_bytes(&bytes), None);
+        assert_eq!(SecretKey::<Q, N>::from_bytes(&bytes), None);
+        let mut bad = bytes.clone();
+        bad[8..10].copy_from_slice(&Q.to_le_bytes()[..2]);
+        assert_eq!(Ciphertext::<Q, N>::from_bytes(&bad), None);
+        let mut bad = pk.to_bytes();
+        let len = bad.len();
+        bad[len - 8..].copy_from_slice(&(-1.0f64).to_le_bytes());
+        assert_eq!(PublicKey::<Q, N>::from_bytes(&bad), None);
+    }
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_base64() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let (sk, pk) = keygen::<_, Q, 4>(&mut rng, SIGMA);
+        let ct = encrypt(&mut rng, &pk, &Poly::from([1, 0, 1, 1]));
+        assert_eq!(SecretKey::from_base64(&sk.to_base64()), Some(sk));
+        assert_eq!(PublicKey::from_base64(&pk.to_base64()), Some(pk));
+        assert_eq!(Ciphertext::from_base64(&ct.to_base64()), Some(ct));
+        // A 16-byte secret key is 24 characters, and its header encodes Q = 12289, N = 4
+        let encoded = sk.to_base64();
+        assert_eq!(encoded.len(), 24);
+        assert!(encoded.starts_with("ATAAAAQAAAA"));
+        assert_eq!(SecretKey::<Q, 4>::from_base64("not base64!"), None);
+        assert_eq!(SecretKey::<Q, 8>::from_base64(&encoded), None);
+    }
+    #[test]
+    #[should_panic(expected = "0/1 coefficients")]
+    fn test_non_binary_message() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let (_, pk) = keygen::<_, Q, 4>(&mut rng, SIGMA);
+        encrypt(&mut rng, &pk, &Poly::from([0, 1, 2, 0]));
+    }
+}
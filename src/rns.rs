@@ -0,0 +1,299 @@
+//! Residue number system rings Z/MZ = Z/m_1Z x ... x Z/m_KZ, for pairwise coprime moduli
+//! with product M. By the CRT this is the ring Z/MZ, but arithmetic is componentwise on
+//! small residues, so a large modulus can be handled with word-sized (and, for
+//! NTT-friendly m_i, NTT-friendly) operations. See primes::build_rns_basis for choosing
+//! moduli.
+//!
+//! This is the CRT-friendly ring the rings module anticipates: a rank K ring over the
+//! residues, implementing From<[u32; K]>.
+
+use crate::rings::FinRankCRing;
+use alga::general::*;
+use num_traits::identities::{One, Zero};
+use std::array;
+use std::convert::From;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// A choice of K pairwise coprime moduli, each at least 1. Arrays are not yet allowed as
+/// const generic parameters, so a basis is a marker type (e.g. a unit struct) carrying
+/// the moduli as an associated const instead.
+pub trait RnsBasis<const K: usize>: Copy + PartialEq + fmt::Debug {
+    const MODULI: [u32; K];
+}
+
+/// An element of Z/m_1Z x ... x Z/m_KZ, stored as its residues (each in [0, m_i)).
+#[derive(Clone, Copy, PartialEq, Debug, Alga)]
+#[alga_traits(RingCommutative(Additive, Multiplicative), Where = "B: RnsBasis<K>")]
+pub struct Rns<B, const K: usize> {
+    residues: [u32; K],
+    basis: PhantomData<B>,
+}
+
+/// a^-1 mod m, or None if a is not invertible mod m, by the extended Euclidean algorithm.
+/// For m = 1 (the zero ring) the gcd is 1 from the start, so every a inverts to 0.
+fn inverse_mod(a: u64, m: u64) -> Option<u64> {
+    // Invariant: r_i = t_i * a mod m
+    let (mut r0, mut r1): (i128, i128) = (m.into(), (a % m).into());
+    let (mut t0, mut t1): (i128, i128) = (0, 1);
+    while r1 != 0 {
+        let quotient = r0 / r1;
+        (r0, r1) = (r1, r0 - quotient * r1);
+        (t0, t1) = (t1, t0 - quotient * t1);
+    }
+    if r0 != 1 {
+        return None;
+    }
+    Some(t0.rem_euclid(m.into()) as u64)
+}
+
+impl<B: RnsBasis<K>, const K: usize> Rns<B, K> {
+    /// The moduli m_1, ..., m_K
+    pub fn moduli() -> [u32; K] {
+        B::MODULI
+    }
+
+    /// The residues of self, each in [0, m_i)
+    pub fn residues(&self) -> [u32; K] {
+        self.residues
+    }
+
+    fn from_residues(residues: [u32; K]) -> Self {
+        Rns {
+            residues,
+            basis: PhantomData,
+        }
+    }
+
+    /// Splits x into its residues mod each m_i.
+    pub fn from_u128(x: u128) -> Self {
+        Self::from_residues(array::from_fn(|i| (x % u128::from(B::MODULI[i])) as u32))
+    }
+
+    /// The representative of self in [0, M), by Garner's mixed-radix form of the CRT:
+    /// after handling the first i residues, x is the representative mod m_1 ... m_i.
+    /// Panics if M does not fit in a u128, or if the moduli are not pairwise coprime.
+    pub fn to_u128(&self) -> u128 {
+        let mut x: u128 = 0;
+        let mut product: u128 = 1;
+        for (&r, &m) in self.residues.iter().zip(B::MODULI.iter()) {
+            let m = u64::from(m);
+            let x_mod_m = (x % u128::from(m)) as u64;
+            let inv = inverse_mod((product % u128::from(m)) as u64, m)
+                .expect("RNS moduli are not pairwise coprime");
+            // t = (r - x) / product mod m, so x + product * t = r mod m
+            let t = ((u64::from(r) + m - x_mod_m) % m) * inv % m;
+            x = product
+                .checked_mul(t.into())
+                .and_then(|step| step.checked_add(x))
+                .expect("the RNS modulus does not fit in a u128");
+            product = product
+                .checked_mul(m.into())
+                .expect("the RNS modulus does not fit in a u128");
+        }
+        x
+    }
+}
+
+impl<B: RnsBasis<K>, const K: usize> From<[u32; K]> for Rns<B, K> {
+    fn from(x: [u32; K]) -> Self {
+        Self::from_residues(array::from_fn(|i| x[i] % B::MODULI[i]))
+    }
+}
+
+impl<B: RnsBasis<K>, const K: usize> FinRankCRing<K> for Rns<B, K> {
+    fn to_coeffs(&self) -> [u32; K] {
+        self.residues
+    }
+}
+
+macro_rules! componentwise {
+    ($func:ident, $bound:ident) => {
+        impl<B: RnsBasis<K>, const K: usize> $bound<Rns<B, K>> for Rns<B, K> {
+            type Output = Rns<B, K>;
+            fn $func(self, other: Self) -> Self::Output {
+                Self::from_residues(array::from_fn(|i| {
+                    let m = u64::from(B::MODULI[i]);
+                    let x = u64::from(self.residues[i]);
+                    let y = u64::from(other.residues[i]);
+                    (u64::$func(x, y) % m) as u32
+                }))
+            }
+        }
+    };
+}
+componentwise!(add, Add);
+componentwise!(mul, Mul);
+
+impl<B: RnsBasis<K>, const K: usize> Neg for Rns<B, K> {
+    type Output = Rns<B, K>;
+    fn neg(self) -> Self::Output {
+        Self::from_residues(array::from_fn(|i| {
+            (B::MODULI[i] - self.residues[i]) % B::MODULI[i]
+        }))
+    }
+}
+
+impl<B: RnsBasis<K>, const K: usize> Sub<Rns<B, K>> for Rns<B, K> {
+    type Output = Rns<B, K>;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn sub(self, other: Self) -> Self::Output {
+        self + other.neg()
+    }
+}
+
+macro_rules! op_assign {
+    ($func:ident, $bound:ident, $method:ident) => {
+        impl<B: RnsBasis<K>, const K: usize> $bound<Rns<B, K>> for Rns<B, K> {
+            fn $func(&mut self, other: Self) {
+                *self = self.$method(other)
+            }
+        }
+    };
+}
+op_assign!(add_assign, AddAssign, add);
+op_assign!(mul_assign, MulAssign, mul);
+op_assign!(sub_assign, SubAssign, sub);
+
+impl<B: RnsBasis<K>, const K: usize> Zero for Rns<B, K> {
+    fn zero() -> Self {
+        Self::from_residues([0; K])
+    }
+    fn is_zero(&self) -> bool {
+        self.residues.iter().all(|r| *r == 0)
+    }
+}
+
+impl<B: RnsBasis<K>, const K: usize> One for Rns<B, K> {
+    fn one() -> Self {
+        Self::from([1; K])
+    }
+}
+
+impl<B: RnsBasis<K>, const K: usize> Identity<Additive> for Rns<B, K> {
+    fn identity() -> Self {
+        Self::zero()
+    }
+}
+
+impl<B: RnsBasis<K>, const K: usize> Identity<Multiplicative> for Rns<B, K> {
+    fn identity() -> Self {
+        Self::one()
+    }
+}
+
+impl<B: RnsBasis<K>, const K: usize> AbstractMagma<Additive> for Rns<B, K> {
+    fn operate(&self, other: &Self) -> Self {
+        *self + *other
+    }
+}
+
+impl<B: RnsBasis<K>, const K: usize> TwoSidedInverse<Additive> for Rns<B, K> {
+    fn two_sided_inverse(&self) -> Self {
+        -*self
+    }
+}
+
+impl<B: RnsBasis<K>, const K: usize> AbstractMagma<Multiplicative> for Rns<B, K> {
+    fn operate(&self, other: &Self) -> Self {
+        *self * *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rings::RingElement;
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    struct Small;
+    impl RnsBasis<3> for Small {
+        const MODULI: [u32; 3] = [7, 11, 13];
+    }
+    type R = Rns<Small, 3>;
+    const M: u128 = 7 * 11 * 13;
+    #[test]
+    fn test_crt_round_trip() {
+        for x in 0..M {
+            assert_eq!(R::from_u128(x).to_u128(), x);
+        }
+        assert_eq!(R::from_u128(1000).residues(), [6, 10, 12]);
+        assert_eq!(R::from_u128(M + 5), R::from_u128(5));
+    }
+    #[test]
+    fn test_arithmetic_matches_mod_m() {
+        for &(a, b) in &[(0, 0), (1, 1000), (500, 700), (1000, 1000), (123, 456)] {
+            let (x, y) = (R::from_u128(a), R::from_u128(b));
+            assert_eq!((x + y).to_u128(), (a + b) % M);
+            assert_eq!((x - y).to_u128(), (a + M - b) % M);
+            assert_eq!((x * y).to_u128(), a * b % M);
+            assert_eq!((-x).to_u128(), (M - a) % M);
+        }
+        assert_eq!(R::one().to_u128(), 1);
+        assert!(R::zero().is_zero());
+        assert_eq!(R::from_u128(2).pow(10).to_u128(), 1024 % M);
+    }
+    #[test]
+    fn test_fin_rank_coeffs() {
+        let x = R::from_u128(1000);
+        assert_eq!(x.to_coeffs(), [6, 10, 12]);
+        assert_eq!(R::from_coeffs(x.to_coeffs()), x);
+        assert_eq!(R::from_coeffs([13, 21, 25]), x);
+    }
+    #[test]
+    fn test_large_moduli() {
+        #[derive(Clone, Copy, PartialEq, Debug)]
+        struct Ntt;
+        impl RnsBasis<4> for Ntt {
+            const MODULI: [u32; 4] = [2147473409, 2147389441, 2147387393, 2147377153];
+        }
+        let x: u128 = 0x0123_4567_89ab_cdef_0123_4567_89ab_cdef;
+        let y = Rns::<Ntt, 4>::from_u128(x);
+        assert_eq!(y.to_u128(), x);
+        assert_eq!((y * y - y * y).to_u128(), 0);
+    }
+    #[test]
+    fn test_moduli_near_u128() {
+        // M is just below 2^128, so the top representatives come close to overflowing
+        #[derive(Clone, Copy, PartialEq, Debug)]
+        struct Top;
+        impl RnsBasis<4> for Top {
+            const MODULI: [u32; 4] = [4294967291, 4294967279, 4294967231, 4294967197];
+        }
+        let m = Top::MODULI.iter().map(|&m| u128::from(m)).product::<u128>();
+        assert_eq!(Rns::<Top, 4>::from_u128(m - 1).to_u128(), m - 1);
+        assert_eq!(Rns::<Top, 4>::from_u128(u128::MAX).to_u128(), u128::MAX % m);
+        assert_eq!((-Rns::<Top, 4>::one()).to_u128(), m - 1);
+    }
+    #[test]
+    #[should_panic(expected = "does not fit in a u128")]
+    fn test_moduli_beyond_u128() {
+        #[derive(Clone, Copy, PartialEq, Debug)]
+        struct Wide;
+        impl RnsBasis<5> for Wide {
+            const MODULI: [u32; 5] = [4294967291, 4294967279, 4294967231, 4294967197, 4294967189];
+        }
+        // Representatives of M's size need more than 128 bits
+        (-Rns::<Wide, 5>::one()).to_u128();
+    }
+    #[test]
+    fn test_inverse_mod() {
+        assert_eq!(inverse_mod(3, 7), Some(5));
+        assert_eq!(inverse_mod(10, 7), Some(5));
+        assert_eq!(inverse_mod(4, 6), None);
+        assert_eq!(inverse_mod(0, 7), None);
+        // The zero ring, where 0 = 1
+        assert_eq!(inverse_mod(0, 1), Some(0));
+        assert_eq!(inverse_mod(5, 1), Some(0));
+    }
+    #[test]
+    #[should_panic(expected = "not pairwise coprime")]
+    fn test_non_coprime_moduli() {
+        #[derive(Clone, Copy, PartialEq, Debug)]
+        struct Bad;
+        impl RnsBasis<2> for Bad {
+            const MODULI: [u32; 2] = [6, 10];
+        }
+        Rns::<Bad, 2>::from_u128(7).to_u128();
+    }
+}
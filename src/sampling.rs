@@ -0,0 +1,247 @@
+//! Samplers for the small error and secret terms of (R)LWE.
+
+use crate::matrices::Matrix;
+use crate::poly::Poly;
+use crate::rings::modular::Modular;
+use num_traits::identities::Zero;
+use rand::Rng;
+use std::array;
+
+/// Tail cut for the discrete Gaussian, in standard deviations. The mass beyond 12 sigma
+/// is below 2^-100, so truncating there is statistically invisible.
+pub(crate) const TAIL_CUT: f64 = 12.0;
+
+/// Samples x from the discrete Gaussian over Z centered at 0 with parameter sigma (so
+/// Pr[x] is proportional to exp(-x^2 / (2 sigma^2))), and reduces it mod Q.
+///
+/// This rejection samples a uniform x in [-12 sigma, 12 sigma] against the Gaussian
+/// density, so is neither fast nor constant time.
+pub fn sample_discrete_gaussian<R: Rng + ?Sized, const Q: u32>(
+    rng: &mut R,
+    sigma: f64,
+) -> Modular<Q> {
+    assert!(sigma > 0.0, "sigma must be positive, got {}", sigma);
+    let bound = (TAIL_CUT * sigma).ceil() as i64;
+    loop {
+        let x = rng.gen_range(-bound..=bound);
+        // Squaring in f64, as x^2 overflows an i64 once sigma passes about 2^28
+        let x_f = x as f64;
+        let density = (-(x_f * x_f) / (2.0 * sigma * sigma)).exp();
+        if rng.gen::<f64>() < density {
+            return Modular::from_i128(x.into());
+        }
+    }
+}
+
+/// A polynomial with independent discrete Gaussian coefficients, see
+/// sample_discrete_gaussian.
+pub fn sample_gaussian_poly<R: Rng + ?Sized, const Q: u32, const N: usize>(
+    rng: &mut R,
+    sigma: f64,
+) -> Poly<Modular<Q>, N> {
+    Poly(array::from_fn(|_| sample_discrete_gaussian(rng, sigma)))
+}
+
+/// A Micciancio-Peikert gadget trapdoor: a uniform A_bar (N x M) and an R (M x W) with
+/// discrete Gaussian entries of parameter sigma, returned as ((A_bar, G - A_bar R), R).
+/// The public matrix is A = [A_bar | G - A_bar R], split into its two column blocks as
+/// the N x (M + W) matrix has no const-generic type on stable Rust, and
+/// A [R; I] = A_bar R + (G - A_bar R) = G for the gadget matrix G = Matrix::gadget().
+/// Panics unless W = N * ceil(log2 Q), see Matrix::gadget.
+pub fn sample_trapdoor<
+    G: Rng + ?Sized,
+    const Q: u32,
+    const N: usize,
+    const M: usize,
+    const W: usize,
+>(
+    rng: &mut G,
+    sigma: f64,
+) -> (
+    (Matrix<Modular<Q>, N, M>, Matrix<Modular<Q>, N, W>),
+    Matrix<Modular<Q>, M, W>,
+) {
+    let gadget = Matrix::gadget();
+    let mut a_bar = Matrix::zero();
+    a_bar.fill_uniform(rng);
+    let r = Matrix(array::from_fn(|_| {
+        array::from_fn(|_| sample_discrete_gaussian(rng, sigma))
+    }));
+    ((a_bar, gadget - a_bar * r), r)
+}
+
+/// A cumulative distribution table for the discrete Gaussian of a fixed sigma, sampling in
+/// constant time. Entry k holds floor(2^64 * Pr[|x| <= k]) for k below the 12 sigma tail
+/// cut, so |x| is the number of entries a uniform u64 is at least. Probabilities are
+/// computed in f64, so accurate to about 2^-53 rather than the 2^-64 resolution of the
+/// table.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CdtSampler {
+    table: Vec<u64>,
+}
+
+impl CdtSampler {
+    /// Precomputes the table for sigma. Panics unless sigma is positive.
+    pub fn new(sigma: f64) -> Self {
+        assert!(sigma > 0.0, "sigma must be positive, got {}", sigma);
+        let bound = (TAIL_CUT * sigma).ceil() as u64;
+        let rho = |k: u64| {
+            let k = k as f64;
+            (-(k * k) / (2.0 * sigma * sigma)).exp()
+        };
+        // The mass of |x| = k is rho(k) for k = 0 and 2 rho(k) otherwise
+        let mass = |k: u64| if k == 0 { rho(0) } else { 2.0 * rho(k) };
+        let total: f64 = (0..=bound).map(mass).sum();
+        let mut cumulative = 0.0;
+        let table = (0..bound)
+            .map(|k| {
+                cumulative += mass(k);
+                // Saturates to u64::MAX for a cumulative probability of 1
+                (cumulative / total * 2f64.powi(64)) as u64
+            })
+            .collect();
+        CdtSampler { table }
+    }
+
+    /// Samples x from the discrete Gaussian and reduces it mod Q, with a running time
+    /// independent of the result: the whole table is scanned with branch-free
+    /// comparisons, and the sign is applied with a mask. Panics unless the 12 sigma tail
+    /// cut is below Q; the panic depends only on sigma and Q.
+    pub fn sample<R: Rng + ?Sized, const Q: u32>(&self, rng: &mut R) -> Modular<Q> {
+        assert!(
+            (self.table.len() as u64) < u64::from(Q),
+            "the tail cut {} does not fit below Q = {}",
+            self.table.len(),
+            Q
+        );
+        let r = u128::from(rng.next_u64());
+        // (r - t) >> 127 is 1 exactly when r < t
+        let below: u32 = self
+            .table
+            .iter()
+            .map(|&t| (r.wrapping_sub(t.into()) >> 127) as u32)
+            .sum();
+        let magnitude = self.table.len() as u32 - below;
+        let negate = 0u32.wrapping_sub(rng.next_u32() & 1);
+        // Q - 0 = Q reduces to 0, so a negative zero is still 0
+        let x = magnitude ^ (negate & (magnitude ^ (Q - magnitude)));
+        Modular::from([x])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    const Q: u32 = 12289;
+    fn signed(x: Modular<Q>) -> f64 {
+        x.centered() as f64
+    }
+    #[test]
+    fn test_gaussian_moments() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        for &sigma in &[1.0, 3.2, 20.0] {
+            let samples: Vec<f64> = (0..20000)
+                .map(|_| signed(sample_discrete_gaussian::<_, Q>(&mut rng, sigma)))
+                .collect();
+            let n = samples.len() as f64;
+            let mean = samples.iter().sum::<f64>() / n;
+            let var = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+            assert!(
+                mean.abs() < 0.05 * sigma,
+                "mean {} for sigma {}",
+                mean,
+                sigma
+            );
+            assert!(
+                (var.sqrt() - sigma).abs() < 0.05 * sigma,
+                "std dev {} for sigma {}",
+                var.sqrt(),
+                sigma
+            );
+        }
+    }
+    #[test]
+    fn test_gaussian_huge_sigma() {
+        // The tail cut of 12 * 2^40 squares past i64::MAX
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2);
+        let sigma = (1u64 << 40) as f64;
+        let samples: Vec<Modular<Q>> = (0..200)
+            .map(|_| sample_discrete_gaussian(&mut rng, sigma))
+            .collect();
+        // Reduced mod Q, such wide samples are close to uniform
+        assert!(samples.iter().any(|c| signed(*c) > 1000.0));
+        assert!(samples.iter().any(|c| signed(*c) < -1000.0));
+    }
+    #[test]
+    fn test_cdt_matches_gaussian() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let sigma = 3.2;
+        let cdt = CdtSampler::new(sigma);
+        let trials = 100000;
+        let mut counts = [0usize; 9];
+        for _ in 0..trials {
+            let x = signed(cdt.sample::<_, Q>(&mut rng));
+            assert!(x.abs() <= TAIL_CUT * sigma);
+            if x.abs() <= 4.0 {
+                counts[(x + 4.0) as usize] += 1;
+            }
+        }
+        // Each bin against its Gaussian probability, to within 5 standard errors
+        let total: f64 = (-40..=40)
+            .map(|k: i32| (-f64::from(k * k) / (2.0 * sigma * sigma)).exp())
+            .sum();
+        for (i, &count) in counts.iter().enumerate() {
+            let k = i as f64 - 4.0;
+            let p = (-(k * k) / (2.0 * sigma * sigma)).exp() / total;
+            let expected = p * trials as f64;
+            let tolerance = 5.0 * (expected * (1.0 - p)).sqrt();
+            assert!(
+                (count as f64 - expected).abs() < tolerance,
+                "{} samples of {}, expected {}",
+                count,
+                k,
+                expected
+            );
+        }
+    }
+    #[test]
+    fn test_cdt_table() {
+        let cdt = CdtSampler::new(1.0);
+        assert_eq!(cdt.table.len(), 12);
+        assert!(cdt.table.windows(2).all(|w| w[0] <= w[1]));
+        // Pr[x = 0] = 1 / sum_k exp(-k^2 / 2), about 0.399
+        let p0 = cdt.table[0] as f64 / 2f64.powi(64);
+        assert!((p0 - 0.3989).abs() < 1e-3, "Pr[0] = {}", p0);
+        // Beyond 8 sigma the remaining mass is below 2^-48
+        assert!(u64::MAX - cdt.table[8] < 1 << 16);
+    }
+    #[test]
+    #[should_panic(expected = "does not fit below Q")]
+    fn test_cdt_tail_cut_too_wide() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(4);
+        CdtSampler::new(2.0).sample::<_, 17>(&mut rng);
+    }
+    #[test]
+    fn test_sample_trapdoor() {
+        // k = 14 gadget columns per row for Q = 12289
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        let ((a_bar, a1), r) = sample_trapdoor::<_, Q, 2, 6, 28>(&mut rng, 3.2);
+        let gadget = Matrix::<Modular<Q>, 2, 28>::gadget();
+        assert_eq!(a_bar * r + a1, gadget);
+        assert!(r
+            .0
+            .iter()
+            .flatten()
+            .all(|c| signed(*c).abs() <= TAIL_CUT * 3.2));
+        assert_ne!(a1, gadget);
+    }
+    #[test]
+    fn test_gaussian_poly() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let p: Poly<Modular<Q>, 256> = sample_gaussian_poly(&mut rng, 3.2);
+        // Every coefficient is within the tail cut, so lies near 0 or near Q
+        assert!(p.0.iter().all(|c| signed(*c).abs() <= TAIL_CUT * 3.2));
+        assert!(p.0.iter().any(|c| signed(*c) < 0.0));
+    }
+}
@@ -0,0 +1,95 @@
+//! serde support, behind the serde feature. Modular elements are serialized as their
+//! representative in [0, Q), and Vector and Poly as sequences of their entries.
+//! Deserialization rejects non-canonical representatives (see
+//! Modular::deserialize_strict) and sequences of the wrong length.
+
+use crate::matrices::Vector;
+use crate::poly::Poly;
+use crate::rings::modular::Modular;
+use crate::rings::FinRankCRing;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::{Serialize, Serializer};
+use std::convert::TryFrom;
+
+impl<const Q: u32> Serialize for Modular<Q> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.to_coeffs()[0])
+    }
+}
+
+impl<'de, const Q: u32> Deserialize<'de> for Modular<Q> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let x = u32::deserialize(deserializer)?;
+        Modular::deserialize_strict(x).map_err(de::Error::custom)
+    }
+}
+
+/// Reads a sequence of exactly N entries.
+fn deserialize_array<'de, D, R, const N: usize>(deserializer: D) -> Result<[R; N], D::Error>
+where
+    D: Deserializer<'de>,
+    R: Deserialize<'de>,
+{
+    let entries = Vec::<R>::deserialize(deserializer)?;
+    let len = entries.len();
+    <[R; N]>::try_from(entries)
+        .map_err(|_| de::Error::invalid_length(len, &format!("{} entries", N).as_str()))
+}
+
+impl<R: Serialize, const DIM: usize> Serialize for Vector<R, DIM> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter())
+    }
+}
+
+impl<'de, R: Deserialize<'de>, const DIM: usize> Deserialize<'de> for Vector<R, DIM> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_array(deserializer).map(Vector)
+    }
+}
+
+impl<R: Serialize, const N: usize> Serialize for Poly<R, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter())
+    }
+}
+
+impl<'de, R: Deserialize<'de>, const N: usize> Deserialize<'de> for Poly<R, N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_array(deserializer).map(Poly)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const Q: u32 = 3329;
+    #[test]
+    fn test_round_trip() {
+        let x = Modular::<Q>::from([1234]);
+        assert_eq!(serde_json::to_string(&x).unwrap(), "1234");
+        assert_eq!(serde_json::from_str::<Modular<Q>>("1234").unwrap(), x);
+        let v = Vector([0, 1, Q - 1].map(|c| Modular::<Q>::from([c])));
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(json, "[0,1,3328]");
+        assert_eq!(
+            serde_json::from_str::<Vector<Modular<Q>, 3>>(&json).unwrap(),
+            v
+        );
+        let p = Poly::<Modular<Q>, 4>::from([5, 6, 7, 8]);
+        let json = serde_json::to_string(&p).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Poly<Modular<Q>, 4>>(&json).unwrap(),
+            p
+        );
+    }
+    #[test]
+    fn test_rejects_malformed() {
+        assert!(serde_json::from_str::<Modular<Q>>("3329").is_err());
+        assert!(serde_json::from_str::<Modular<Q>>("4294967295").is_err());
+        assert!(serde_json::from_str::<Vector<Modular<Q>, 3>>("[0,1,3329]").is_err());
+        // Wrong length
+        assert!(serde_json::from_str::<Vector<Modular<Q>, 3>>("[0,1]").is_err());
+        assert!(serde_json::from_str::<Poly<Modular<Q>, 2>>("[0,1,2]").is_err());
+    }
+}
@@ -0,0 +1,64 @@
+//! Checks for validating parameter choices, used internally as debug assertions and
+//! exported so downstream code can run the same checks on its own parameters.
+
+use crate::rings::modular::Modular;
+use num_traits::identities::One;
+
+/// Panics unless root has multiplicative order exactly `order`, i.e. root^order = 1 and
+/// root^(order / p) != 1 for each prime p dividing order.
+pub fn assert_primitive_root<const Q: u32>(root: Modular<Q>, order: u64) {
+    assert!(order > 0, "the order must be positive");
+    assert!(root.pow(order).is_one(), "{}^{} is not 1", root, order);
+    // Trial division is fine for the orders the NTT needs (2N)
+    let mut rest = order;
+    let mut p = 2;
+    while rest > 1 {
+        if p * p > rest {
+            p = rest;
+        }
+        if rest.is_multiple_of(p) {
+            assert!(
+                !root.pow(order / p).is_one(),
+                "{} has order dividing {}, not {}",
+                root,
+                order / p,
+                order
+            );
+            while rest.is_multiple_of(p) {
+                rest /= p;
+            }
+        }
+        p += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_primitive_roots() {
+        // 3 generates (Z/17Z)*, and 3^2 = 9 has order 8
+        assert_primitive_root(Modular::<17>::from([3]), 16);
+        assert_primitive_root(Modular::<17>::from([9]), 8);
+        // Order 12 = 2^2 * 3 elements of (Z/13Z)*: 2 and 6
+        assert_primitive_root(Modular::<13>::from([2]), 12);
+        assert_primitive_root(Modular::<13>::from([6]), 12);
+        assert_primitive_root(Modular::<13>::one(), 1);
+    }
+    #[test]
+    #[should_panic(expected = "4 (mod 17) has order dividing 8, not 16")]
+    fn test_order_too_small() {
+        assert_primitive_root(Modular::<17>::from([4]), 16);
+    }
+    #[test]
+    #[should_panic(expected = "has order dividing 4, not 12")]
+    fn test_order_missing_odd_prime() {
+        // 5 has order 4 mod 13
+        assert_primitive_root(Modular::<13>::from([5]), 12);
+    }
+    #[test]
+    #[should_panic(expected = "3 (mod 17)^10 is not 1")]
+    fn test_not_a_root() {
+        assert_primitive_root(Modular::<17>::from([3]), 10);
+    }
+}